@@ -0,0 +1,2 @@
+-- Person the todo is assigned to, empty when unassigned
+ALTER TABLE todos ADD COLUMN assigned TEXT NOT NULL DEFAULT '';
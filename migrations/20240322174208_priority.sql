@@ -0,0 +1,2 @@
+-- Todo priority: 0 = low, 1 = normal, 2 = high, 3 = urgent
+ALTER TABLE todos ADD COLUMN priority INTEGER NOT NULL DEFAULT 1;
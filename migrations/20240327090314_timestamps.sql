@@ -0,0 +1,20 @@
+-- Track when todos are created and last modified.
+-- SQLite can't add a column defaulting to CURRENT_TIMESTAMP, so rebuild the table.
+CREATE TABLE todos_new
+(
+    id          INTEGER PRIMARY KEY NOT NULL,
+    title       TEXT                NOT NULL,
+    notes       TEXT                NOT NULL DEFAULT 'note',
+    completed   BOOLEAN             NOT NULL DEFAULT 0,
+    assigned    TEXT                NOT NULL DEFAULT '',
+    priority    INTEGER             NOT NULL DEFAULT 1,
+    created_at  DATETIME            NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  DATETIME            NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO todos_new (id, title, notes, completed, assigned, priority)
+SELECT id, title, notes, completed, assigned, priority FROM todos;
+
+DROP TABLE todos;
+
+ALTER TABLE todos_new RENAME TO todos;
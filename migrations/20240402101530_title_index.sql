@@ -0,0 +1,3 @@
+-- Speed up title lookups such as the duplicate check of `create_unique`.
+-- Not unique on purpose, plain `create` still allows duplicate titles.
+CREATE INDEX IF NOT EXISTS todos_title ON todos (title);
@@ -0,0 +1,6 @@
+-- Subtasks and manual ordering.
+-- Deleting a todo deletes its subtasks, positions order siblings.
+ALTER TABLE todos ADD COLUMN parent_id INTEGER REFERENCES todos (id) ON DELETE CASCADE;
+ALTER TABLE todos ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS todos_parent_id ON todos (parent_id);
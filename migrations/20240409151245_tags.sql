@@ -0,0 +1,9 @@
+-- Free form tags, a todo has each tag at most once.
+CREATE TABLE IF NOT EXISTS todos_tags
+(
+    todo_id INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    tag     TEXT    NOT NULL,
+    PRIMARY KEY (todo_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS todos_tags_tag ON todos_tags (tag);
@@ -0,0 +1,5 @@
+-- When a todo was completed, NULL while it is open.
+ALTER TABLE todos ADD COLUMN completed_at DATETIME;
+
+-- best guess for todos completed before the column existed
+UPDATE todos SET completed_at = updated_at WHERE completed = 1;
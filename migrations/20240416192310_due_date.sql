@@ -0,0 +1,4 @@
+-- Day a todo should be done by, NULL when it has no deadline.
+ALTER TABLE todos ADD COLUMN due_date DATE;
+
+CREATE INDEX IF NOT EXISTS todos_due_date_idx ON todos (due_date);
@@ -0,0 +1,32 @@
+-- When the todos last changed, deletions included, for `Last-Modified`.
+-- Single row, missing until the first todo is stored.
+CREATE TABLE IF NOT EXISTS todos_modified
+(
+    id          INTEGER PRIMARY KEY NOT NULL CHECK (id = 1),
+    modified_at DATETIME            NOT NULL
+);
+
+INSERT INTO todos_modified (id, modified_at)
+SELECT 1, MAX(updated_at) FROM todos HAVING COUNT(*) > 0;
+
+-- inserts and updates bring their own `updated_at`, imports can set older ones
+CREATE TRIGGER IF NOT EXISTS todos_modified_insert AFTER INSERT ON todos
+BEGIN
+    INSERT INTO todos_modified (id, modified_at)
+        SELECT 1, NEW.updated_at WHERE NOT EXISTS (SELECT 1 FROM todos_modified);
+    UPDATE todos_modified SET modified_at = MAX(modified_at, NEW.updated_at) WHERE id = 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS todos_modified_update AFTER UPDATE ON todos
+BEGIN
+    INSERT INTO todos_modified (id, modified_at)
+        SELECT 1, NEW.updated_at WHERE NOT EXISTS (SELECT 1 FROM todos_modified);
+    UPDATE todos_modified SET modified_at = MAX(modified_at, NEW.updated_at) WHERE id = 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS todos_modified_delete AFTER DELETE ON todos
+BEGIN
+    INSERT INTO todos_modified (id, modified_at)
+        SELECT 1, CURRENT_TIMESTAMP WHERE NOT EXISTS (SELECT 1 FROM todos_modified);
+    UPDATE todos_modified SET modified_at = MAX(modified_at, CURRENT_TIMESTAMP) WHERE id = 1;
+END;
@@ -0,0 +1,155 @@
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{FromRequest, FromRequestParts};
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::pagination::PaginationError;
+use crate::todo::{RepositoryError, ValidationError};
+
+/// Stable machine-readable error code
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    NotFound,
+    Validation,
+    Conflict,
+    RateLimited,
+    Internal,
+}
+
+/// Body of every error response
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ErrorBody {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Individual violations when a payload broke several rules
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ApiError {
+    #[error("todo not found")]
+    NotFound,
+    #[error("{0}")]
+    Validation(String),
+    #[error("{0}")]
+    Invalid(#[from] ValidationError),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("too many requests, retry in {retry_after}s")]
+    RateLimited { retry_after: u64 },
+    #[error("internal error")]
+    Internal(anyhow::Error),
+}
+
+impl ApiError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ApiError::NotFound => ErrorCode::NotFound,
+            ApiError::Validation(_) | ApiError::Invalid(_) => ErrorCode::Validation,
+            ApiError::Conflict(_) => ErrorCode::Conflict,
+            ApiError::RateLimited { .. } => ErrorCode::RateLimited,
+            ApiError::Internal(_) => ErrorCode::Internal,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::Validation(_) | ApiError::Invalid(_) => StatusCode::BAD_REQUEST,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Map repository errors to API errors
+impl From<RepositoryError> for ApiError {
+    fn from(err: RepositoryError) -> Self {
+        match err {
+            RepositoryError::NotFound(_) => ApiError::NotFound,
+            RepositoryError::Conflict(message) => ApiError::Conflict(message),
+            RepositoryError::Validation(message) => ApiError::Validation(message),
+            RepositoryError::Database(err) => ApiError::Internal(err.into()),
+            RepositoryError::Io(err) => ApiError::Internal(err.into()),
+        }
+    }
+}
+
+/// Errors from outside the repository are internal unless they wrap a [`RepositoryError`]
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<RepositoryError>() {
+            Ok(err) => err.into(),
+            Err(err) => ApiError::Internal(err),
+        }
+    }
+}
+
+impl From<PaginationError> for ApiError {
+    fn from(err: PaginationError) -> Self {
+        ApiError::Validation(err.to_string())
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::Validation(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::Validation(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::Validation(rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if let ApiError::Internal(err) = &self {
+            tracing::error!("internal error: {err:?}");
+        }
+        let details = match &self {
+            ApiError::Invalid(err) => err.violations.clone(),
+            _ => Vec::new(),
+        };
+        let body = ErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+            details,
+        };
+        let mut response = (self.status(), Json(body)).into_response();
+        if let ApiError::RateLimited { retry_after } = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
+    }
+}
+
+/// `Json` extractor answering rejections with an [`ErrorBody`]
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct ApiJson<T>(pub T);
+
+/// `Path` extractor answering rejections with an [`ErrorBody`]
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct ApiPath<T>(pub T);
+
+/// `Query` extractor answering rejections with an [`ErrorBody`]
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct ApiQuery<T>(pub T);
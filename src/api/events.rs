@@ -0,0 +1,26 @@
+use std::convert::Infallible;
+
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::todo::TodoRepository;
+
+/// Stream every [`crate::todo::TodoEvent`] as an SSE `data:` frame with a JSON payload
+///
+/// The subscription ends when the client goes away and the stream is dropped.
+/// Clients too slow to keep up miss events instead of stalling everyone else.
+pub async fn todo_events(
+    State(repo): State<TodoRepository>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = BroadcastStream::new(repo.subscribe()).filter_map(|event| match event {
+        Ok(event) => Event::default().json_data(event).ok().map(Ok),
+        Err(BroadcastStreamRecvError::Lagged(missed)) => {
+            tracing::warn!("event subscriber lagged behind, {missed} event(s) dropped");
+            None
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,1671 @@
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::header::{
+    ACCEPT, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, IF_MODIFIED_SINCE, LAST_MODIFIED,
+    X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::Json;
+use axum::{
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, patch, post, put},
+    Router,
+};
+
+use chrono::{DateTime, NaiveDateTime};
+use serde::{Deserialize, Deserializer, Serialize};
+use tower::ServiceBuilder;
+use tower_http::set_header::SetResponseHeaderLayer;
+
+use crate::pagination::Pagination;
+use crate::sort::Sort;
+use crate::todo::{
+    write_todos, AssignTodo, BatchOutcome, BatchRequest, ChangeKind, CreateTodo, ExportFormat,
+    PoolStats, SetNotes, SetOrder, SetPriority, TagChanges, TagTodos, Todo, TodoField,
+    TodoRepository, TodoStats, UpdateTodo,
+};
+
+pub mod error;
+pub mod events;
+pub mod pretty;
+pub mod rate_limit;
+pub mod shutdown;
+pub mod timing;
+
+use error::{ApiError, ApiJson, ApiPath, ApiQuery};
+
+/// How long `/ready` waits for a free connection
+const READY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Largest `limit` a client may ask for in one page
+const MAX_PAGE_LIMIT: u32 = 1000;
+
+/// Body of the `/ready` response
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Readiness {
+    pub db: String,
+    pub connections: PoolStats,
+}
+
+/// Query parameters selecting todos by id, e.g. `?ids=1,3,5` or `?min_id=1&max_id=1000`
+///
+/// The id range is inclusive, a missing bound leaves that side open.
+#[derive(Deserialize, Debug, Default)]
+pub struct IdsQuery {
+    #[serde(default, deserialize_with = "comma_separated_ids")]
+    pub ids: Option<Vec<i64>>,
+    pub min_id: Option<i64>,
+    pub max_id: Option<i64>,
+}
+
+/// Query parameter choosing the fields of listed todos, e.g. `?fields=id,title`
+#[derive(Deserialize, Debug, Default)]
+pub struct FieldsQuery {
+    #[serde(default, deserialize_with = "comma_separated_fields")]
+    pub fields: Option<Vec<TodoField>>,
+}
+
+/// Body of the `/todos/stats` response, todo counts next to pool usage
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StatsResponse {
+    #[serde(flatten)]
+    pub todos: TodoStats,
+    pub pool: PoolMetrics,
+}
+
+/// Connection pool usage for operators
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolMetrics {
+    /// Most connections the pool opens
+    pub pool_size: u32,
+    /// Open connections waiting for a query
+    pub idle: usize,
+    /// Open connections, idle or in use
+    pub num_connections: u32,
+}
+
+/// Query parameter listing relations to embed, e.g. `?expand=tags,children`
+#[derive(Deserialize, Debug, Default)]
+pub struct ExpandQuery {
+    pub expand: Option<String>,
+}
+
+/// Todo with the relations asked for by [`ExpandQuery`]
+///
+/// Without any expansion this serializes exactly like [`Todo`].
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TodoDetails {
+    #[serde(flatten)]
+    pub todo: Todo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<Todo>>,
+}
+
+/// Query parameters of the sync feed, e.g. `?since=2024-03-05T08:30:00&kind=created`
+#[derive(Deserialize, Debug)]
+pub struct ChangesQuery {
+    pub since: NaiveDateTime,
+    pub kind: Option<ChangeKind>,
+}
+
+/// Query parameter with a search term, e.g. `?q=groceries`
+#[derive(Deserialize, Debug)]
+pub struct SearchQuery {
+    pub q: String,
+}
+
+fn comma_separated_ids<'de, D>(deserializer: D) -> Result<Option<Vec<i64>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    value
+        .split(',')
+        .map(|id| {
+            id.trim()
+                .parse()
+                .map_err(|_| serde::de::Error::custom(format!("invalid todo id `{id}`")))
+        })
+        .collect::<Result<Vec<i64>, _>>()
+        .map(Some)
+}
+
+fn comma_separated_fields<'de, D>(deserializer: D) -> Result<Option<Vec<TodoField>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    TodoField::parse_list(&value)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Build the API router on top of the given repository
+pub fn router(repo: TodoRepository) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+        .route("/schema/todo", get(todo_schema))
+        .route("/todos", get(get_todos).post(add_todo))
+        .route("/todos/completed", delete(delete_completed))
+        .route("/todos/events", get(events::todo_events))
+        .route("/todos/changes", get(changes))
+        .route("/todos/complete", post(complete_matching))
+        .route("/todos/reopen", post(reopen_all))
+        .route("/todos/random", get(random_todo))
+        .route("/todos/stats", get(todo_stats))
+        .route("/todos/priority", patch(set_priority_many))
+        .route("/todos/tags", patch(tag_many))
+        .route("/todos/order", put(set_order))
+        .route("/todos/batch", post(batch))
+        .route("/todos/by-title/:title", get(get_todo_by_title))
+        .route(
+            "/todos/:id",
+            get(get_todo).patch(update_todo).delete(delete_todo),
+        )
+        .route("/todos/:id/complete", post(complete_todo))
+        .route("/todos/:id/reopen", post(reopen_todo))
+        .route("/todos/:id/assign", patch(assign_todo))
+        .route("/todos/:id/notes", patch(set_notes))
+        .route("/todos/persist", post(persist))
+        .with_state(repo)
+        .layer(middleware::from_fn_with_state(
+            pretty::pretty_from_env(),
+            pretty::pretty_json,
+        ))
+        .layer(middleware::from_fn_with_state(
+            rate_limit::RateLimiter::new(rate_limit::rate_limit_from_env()),
+            rate_limit::rate_limit,
+        ))
+        // handlers serving cacheable responses set their own `Cache-Control`
+        .layer(
+            ServiceBuilder::new()
+                .layer(SetResponseHeaderLayer::overriding(
+                    X_CONTENT_TYPE_OPTIONS,
+                    HeaderValue::from_static("nosniff"),
+                ))
+                .layer(SetResponseHeaderLayer::overriding(
+                    X_FRAME_OPTIONS,
+                    HeaderValue::from_static("DENY"),
+                ))
+                .layer(SetResponseHeaderLayer::if_not_present(
+                    CACHE_CONTROL,
+                    HeaderValue::from_static("no-store"),
+                )),
+        )
+        // outermost, so rate limiting and pretty printing are timed too
+        .layer(middleware::from_fn(timing::response_time))
+}
+
+/// Liveness probe, answers as long as the process runs
+async fn health() -> &'static str {
+    "ok"
+}
+
+/// Readiness probe, 503 when no connection is available in time
+async fn ready(State(mut repo): State<TodoRepository>) -> impl IntoResponse {
+    let (status, db) = match repo.ping(READY_TIMEOUT).await {
+        Ok(()) => (StatusCode::OK, "ok"),
+        Err(err) => {
+            tracing::warn!("readiness check failed: {err:?}");
+            (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+        }
+    };
+    let body = Readiness {
+        db: db.to_owned(),
+        connections: repo.pool_stats(),
+    };
+    (status, Json(body))
+}
+
+/// List todos, most urgent first unless `sort` says otherwise
+///
+/// `?ids=1,3,5` returns just those todos ignoring pagination. `?fields=id,title`
+/// selects only those fields of the page, it can't be combined with ids and CSV.
+/// Responses carry `Last-Modified` and answer 304 when `If-Modified-Since` is not older.
+async fn get_todos(
+    ApiQuery(pagination): ApiQuery<Pagination>,
+    ApiQuery(sort): ApiQuery<Sort>,
+    ApiQuery(ids): ApiQuery<IdsQuery>,
+    ApiQuery(FieldsQuery { fields }): ApiQuery<FieldsQuery>,
+    headers: HeaderMap,
+    State(mut repo): State<TodoRepository>,
+) -> Result<Response, ApiError> {
+    // a bad request is a 400 even when the client's copy is current
+    pagination.validate(MAX_PAGE_LIMIT)?;
+    let by_id = ids.ids.is_some() || ids.min_id.is_some() || ids.max_id.is_some();
+    if fields.is_some() && by_id {
+        return Err(ApiError::Validation(
+            "fields can't be combined with ids, min_id or max_id".to_owned(),
+        ));
+    }
+    if fields.is_some() && wants_csv(&headers) {
+        return Err(ApiError::Validation(
+            "fields only apply to JSON, CSV always has every column".to_owned(),
+        ));
+    }
+    let (min, max) = (
+        ids.min_id.unwrap_or(i64::MIN),
+        ids.max_id.unwrap_or(i64::MAX),
+    );
+    if min > max {
+        return Err(ApiError::Validation(
+            "min_id must not be greater than max_id".to_owned(),
+        ));
+    }
+    let last_modified = repo.last_modified().await?;
+    if let Some(last_modified) = last_modified {
+        if is_not_modified(&headers, last_modified) {
+            let mut response = StatusCode::NOT_MODIFIED.into_response();
+            cache_headers(&mut response, Some(last_modified));
+            return Ok(response);
+        }
+    }
+    if let Some(fields) = fields {
+        let todos = repo
+            .list_fields(pagination, sort.sort.unwrap_or_default(), &fields)
+            .await?;
+        let mut response = Json(todos).into_response();
+        cache_headers(&mut response, last_modified);
+        return Ok(response);
+    }
+    let todos = if let Some(ids) = ids.ids {
+        repo.get_many(&ids).await?
+    } else if by_id {
+        repo.list_id_range(min, max).await?
+    } else {
+        repo.list_sorted(pagination, sort.sort.unwrap_or_default())
+            .await?
+    };
+    let mut response = if wants_csv(&headers) {
+        let mut csv = Vec::new();
+        write_todos(ExportFormat::Csv, true, &todos, &mut csv).map_err(anyhow::Error::from)?;
+        (
+            [
+                (CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (CONTENT_DISPOSITION, "attachment; filename=\"todos.csv\""),
+            ],
+            csv,
+        )
+            .into_response()
+    } else {
+        Json(todos).into_response()
+    };
+    cache_headers(&mut response, last_modified);
+    Ok(response)
+}
+
+/// `Last-Modified` and `Cache-Control` of todo lists
+fn cache_headers(response: &mut Response, last_modified: Option<NaiveDateTime>) {
+    if let Some(last_modified) = last_modified {
+        if let Ok(value) = HeaderValue::from_str(&http_date(last_modified)) {
+            response.headers_mut().insert(LAST_MODIFIED, value);
+        }
+    }
+    // clients may keep the list as long as they revalidate with `If-Modified-Since`
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+}
+
+/// Whether the client asked for CSV, anything else gets JSON
+fn wants_csv(headers: &HeaderMap) -> bool {
+    headers
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| {
+            accept
+                .split(',')
+                .any(|media| media.split(';').next().unwrap_or_default().trim() == "text/csv")
+        })
+}
+
+/// Todos changed since a UTC timestamp, for incremental sync
+async fn changes(
+    ApiQuery(query): ApiQuery<ChangesQuery>,
+    ApiQuery(pagination): ApiQuery<Pagination>,
+    State(mut repo): State<TodoRepository>,
+) -> Result<Json<Vec<Todo>>, ApiError> {
+    pagination.validate(MAX_PAGE_LIMIT)?;
+    let todos = repo.changes(query.since, query.kind, pagination).await?;
+    Ok(Json(todos))
+}
+
+/// Format a UTC timestamp as an HTTP date, e.g. `Tue, 05 Mar 2024 08:30:00 GMT`
+fn http_date(time: NaiveDateTime) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Whether `If-Modified-Since` is at or after `last_modified`
+///
+/// A missing or unparsable header never counts as a match.
+fn is_not_modified(headers: &HeaderMap, last_modified: NaiveDateTime) -> bool {
+    headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+        .is_some_and(|since| since.naive_utc() >= last_modified)
+}
+
+async fn get_todo(
+    ApiPath(id): ApiPath<i64>,
+    ApiQuery(expand): ApiQuery<ExpandQuery>,
+    State(mut repo): State<TodoRepository>,
+) -> Result<Json<TodoDetails>, ApiError> {
+    let todo = repo.get(id).await?;
+    let mut details = TodoDetails {
+        todo,
+        tags: None,
+        children: None,
+    };
+    let relations = expand.expand.unwrap_or_default();
+    for relation in relations
+        .split(',')
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+    {
+        match relation {
+            "tags" => details.tags = Some(repo.tags(id).await?),
+            "children" => details.children = Some(repo.children(id).await?),
+            other => {
+                return Err(ApiError::Validation(format!(
+                    "cannot expand `{other}`, use tags or children"
+                )))
+            }
+        }
+    }
+    Ok(Json(details))
+}
+
+/// Todo titled exactly like the path segment, the oldest one when several match
+///
+/// The title is percent-decoded, so spaces and slashes must be sent as `%20` and `%2F`.
+async fn get_todo_by_title(
+    ApiPath(title): ApiPath<String>,
+    State(mut repo): State<TodoRepository>,
+) -> Result<Json<Todo>, ApiError> {
+    let todo = repo.find_by_title(&title).await?;
+    todo.map(Json).ok_or(ApiError::NotFound)
+}
+
+/// JSON Schemas of the todo payloads, derived from the structs the API uses
+async fn todo_schema() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "todo": schemars::schema_for!(Todo),
+        "create_todo": schemars::schema_for!(CreateTodo),
+        "update_todo": schemars::schema_for!(UpdateTodo),
+    }))
+}
+
+/// Todo counts by completion and priority
+///
+/// There is no auth layer yet, so pool metrics are public like the rest of the API.
+async fn todo_stats(
+    State(mut repo): State<TodoRepository>,
+) -> Result<Json<StatsResponse>, ApiError> {
+    let todos = repo.stats().await?;
+    let pool = repo.pool();
+    let pool = PoolMetrics {
+        pool_size: pool.options().get_max_connections(),
+        idle: pool.num_idle(),
+        num_connections: pool.size(),
+    };
+    Ok(Json(StatsResponse { todos, pool }))
+}
+
+/// Pick a random open todo, 404 when nothing is left to do
+async fn random_todo(State(mut repo): State<TodoRepository>) -> Result<Json<Todo>, ApiError> {
+    let todo = repo.random_open().await?.ok_or(ApiError::NotFound)?;
+    Ok(Json(todo))
+}
+
+async fn add_todo(
+    State(mut todos): State<TodoRepository>,
+    ApiJson(todo): ApiJson<CreateTodo>,
+) -> Result<impl IntoResponse, ApiError> {
+    todo.validate()?;
+    let todo = todos.create(todo).await?;
+    Ok((StatusCode::CREATED, Json(todo)))
+}
+
+async fn delete_todo(
+    ApiPath(id): ApiPath<i64>,
+    State(mut repo): State<TodoRepository>,
+) -> Result<StatusCode, ApiError> {
+    repo.delete(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Clear all completed todos
+async fn delete_completed(
+    State(mut repo): State<TodoRepository>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let deleted = repo.delete_completed().await?;
+    Ok(Json(serde_json::json!({ "deleted": deleted })))
+}
+
+/// Complete every open todo whose title contains `q`
+async fn complete_matching(
+    ApiQuery(search): ApiQuery<SearchQuery>,
+    State(mut repo): State<TodoRepository>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if search.q.trim().is_empty() {
+        return Err(ApiError::Validation("q must not be empty".to_owned()));
+    }
+    let completed = repo.complete_matching(&search.q).await?;
+    Ok(Json(serde_json::json!({ "completed": completed })))
+}
+
+/// Reopen every completed todo
+async fn reopen_all(
+    State(mut repo): State<TodoRepository>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let reopened = repo.reopen_all().await?;
+    Ok(Json(serde_json::json!({ "reopened": reopened })))
+}
+
+/// Set the same priority on several todos
+async fn set_priority_many(
+    State(mut repo): State<TodoRepository>,
+    ApiJson(body): ApiJson<SetPriority>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let updated = repo.set_priority_many(&body.ids, body.priority).await?;
+    Ok(Json(serde_json::json!({ "updated": updated })))
+}
+
+/// Reorder todos for drag and drop, `order` has to list every todo once
+async fn set_order(
+    State(mut repo): State<TodoRepository>,
+    ApiJson(body): ApiJson<SetOrder>,
+) -> Result<StatusCode, ApiError> {
+    repo.set_order(&body.order).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Apply creates, updates and deletes in one transaction, see [`TodoRepository::batch`]
+async fn batch(
+    State(mut repo): State<TodoRepository>,
+    ApiJson(body): ApiJson<BatchRequest>,
+) -> Result<Json<Vec<BatchOutcome>>, ApiError> {
+    let outcomes = repo.batch(body.operations, body.continue_on_error).await?;
+    Ok(Json(outcomes))
+}
+
+/// Add and/or remove a tag on several todos
+async fn tag_many(
+    State(mut repo): State<TodoRepository>,
+    ApiJson(body): ApiJson<TagTodos>,
+) -> Result<Json<TagChanges>, ApiError> {
+    if body.add.is_none() && body.remove.is_none() {
+        return Err(ApiError::Validation(
+            "pass a tag to add or remove".to_owned(),
+        ));
+    }
+    let changes = repo
+        .tag_many(&body.ids, body.add.as_deref(), body.remove.as_deref())
+        .await?;
+    Ok(Json(changes))
+}
+
+/// Update todo, 304 when the request would not change anything
+async fn update_todo(
+    ApiPath(id): ApiPath<i64>,
+    State(mut repo): State<TodoRepository>,
+    ApiJson(todo): ApiJson<UpdateTodo>,
+) -> Result<Response, ApiError> {
+    todo.validate()?;
+    match repo.update(id, todo).await? {
+        Some(todo) => Ok(Json(todo).into_response()),
+        None => Ok(StatusCode::NOT_MODIFIED.into_response()),
+    }
+}
+
+/// Mark todo as done without a request body
+async fn complete_todo(
+    ApiPath(id): ApiPath<i64>,
+    State(mut repo): State<TodoRepository>,
+) -> Result<Json<Todo>, ApiError> {
+    set_completed(&mut repo, id, true).await
+}
+
+/// Mark todo as open again without a request body
+async fn reopen_todo(
+    ApiPath(id): ApiPath<i64>,
+    State(mut repo): State<TodoRepository>,
+) -> Result<Json<Todo>, ApiError> {
+    set_completed(&mut repo, id, false).await
+}
+
+async fn set_completed(
+    repo: &mut TodoRepository,
+    id: i64,
+    completed: bool,
+) -> Result<Json<Todo>, ApiError> {
+    let update = UpdateTodo {
+        completed: Some(completed),
+        ..Default::default()
+    };
+    // already in the requested state when nothing was updated
+    let todo = match repo.update(id, update).await? {
+        Some(todo) => todo,
+        None => repo.get(id).await?,
+    };
+    Ok(Json(todo))
+}
+
+/// Reassign todo, empty `assigned` unassigns it
+async fn assign_todo(
+    ApiPath(id): ApiPath<i64>,
+    State(mut repo): State<TodoRepository>,
+    ApiJson(assign): ApiJson<AssignTodo>,
+) -> Result<Json<Todo>, ApiError> {
+    let todo = repo.assign(id, &assign.assigned).await?;
+    Ok(Json(todo))
+}
+
+/// Replace only the notes of a todo
+async fn set_notes(
+    ApiPath(id): ApiPath<i64>,
+    State(mut repo): State<TodoRepository>,
+    ApiJson(notes): ApiJson<SetNotes>,
+) -> Result<Json<Todo>, ApiError> {
+    let todo = repo.set_notes(id, &notes.notes).await?;
+    Ok(Json(todo))
+}
+
+async fn persist() -> impl IntoResponse {
+    "Call method persist"
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::api::error::{ErrorBody, ErrorCode};
+    use crate::todo::Priority;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tokio_stream::StreamExt;
+    use tower::ServiceExt;
+
+    async fn create_repo() -> TodoRepository {
+        TodoRepository::in_memory_with(&[CreateTodo {
+            title: "Test todo 1".to_owned(),
+            ..Default::default()
+        }])
+        .await
+        .unwrap()
+    }
+
+    async fn send_raw(repo: TodoRepository, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = router(repo).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    async fn send(repo: TodoRepository, request: Request<Body>) -> (StatusCode, Option<Todo>) {
+        let (status, body) = send_raw(repo, request).await;
+        (status, serde_json::from_slice(&body).ok())
+    }
+
+    async fn send_error(repo: TodoRepository, request: Request<Body>) -> (StatusCode, ErrorBody) {
+        let (status, body) = send_raw(repo, request).await;
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn json_request(method: &str, uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn post(repo: TodoRepository, uri: &str) -> (StatusCode, Option<Todo>) {
+        send(repo, Request::post(uri).body(Body::empty()).unwrap()).await
+    }
+
+    async fn patch(
+        repo: TodoRepository,
+        uri: &str,
+        body: serde_json::Value,
+    ) -> (StatusCode, Option<Todo>) {
+        send(repo, json_request("PATCH", uri, body)).await
+    }
+
+    #[tokio::test]
+    async fn test_complete() {
+        let repo = create_repo().await;
+        let (status, todo) = post(repo.clone(), "/todos/1/complete").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(todo.unwrap().completed);
+    }
+
+    #[tokio::test]
+    async fn test_reopen() {
+        let repo = create_repo().await;
+        let (status, _) = post(repo.clone(), "/todos/1/complete").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, todo) = post(repo.clone(), "/todos/1/reopen").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!todo.unwrap().completed);
+    }
+
+    #[tokio::test]
+    async fn test_complete_missing() {
+        let repo = create_repo().await;
+        let (status, todo) = post(repo.clone(), "/todos/42/complete").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(todo.is_none());
+        let (status, _) = post(repo, "/todos/42/reopen").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_assign() {
+        let repo = create_repo().await;
+        let (status, todo) = patch(
+            repo.clone(),
+            "/todos/1/assign",
+            serde_json::json!({"assigned": "alice"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(todo.unwrap().assigned, "alice");
+        // reassign
+        let (status, todo) = patch(
+            repo.clone(),
+            "/todos/1/assign",
+            serde_json::json!({"assigned": "bob"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(todo.unwrap().assigned, "bob");
+        // unassign
+        let (status, todo) = patch(
+            repo.clone(),
+            "/todos/1/assign",
+            serde_json::json!({"assigned": ""}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(todo.unwrap().assigned, "");
+        let (status, _) = patch(
+            repo,
+            "/todos/42/assign",
+            serde_json::json!({"assigned": "alice"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_error_code_not_found() {
+        let repo = create_repo().await;
+        let request = Request::get("/todos/42").body(Body::empty()).unwrap();
+        let (status, error) = send_error(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error.code, ErrorCode::NotFound);
+        let (status, error) = send_error(
+            repo,
+            Request::post("/todos/42/complete")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error.code, ErrorCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_error_code_validation() {
+        let repo = create_repo().await;
+        let request = json_request("POST", "/todos", serde_json::json!({"title": "  "}));
+        let (status, error) = send_error(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+        // malformed body is reported the same way
+        let request = json_request("POST", "/todos", serde_json::json!({"name": "x"}));
+        let (status, error) = send_error(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+        let request = Request::get("/todos/abc").body(Body::empty()).unwrap();
+        let (_, error) = send_error(repo, request).await;
+        assert_eq!(error.code, ErrorCode::Validation);
+    }
+
+    #[tokio::test]
+    async fn test_update_lists_all_violations() {
+        let repo = create_repo().await;
+        let notes = "x".repeat(crate::todo::MAX_NOTES_LEN + 1);
+        let (status, error) = send_error(
+            repo,
+            json_request(
+                "PATCH",
+                "/todos/1",
+                serde_json::json!({"title": "", "notes": notes}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+        assert_eq!(error.details.len(), 2);
+        assert!(error.details[0].contains("title"));
+        assert!(error.details[1].contains("notes"));
+    }
+
+    #[tokio::test]
+    async fn test_ready() {
+        let repo = create_repo().await;
+        let request = Request::get("/ready").body(Body::empty()).unwrap();
+        let (status, body) = send_raw(repo, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["db"], "ok");
+        assert!(body["connections"]["idle"].is_u64());
+        assert!(body["connections"]["size"].as_u64().unwrap() >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_default_priority_order() {
+        let repo = TodoRepository::in_memory_with(&[
+            CreateTodo {
+                title: "Low".to_owned(),
+                priority: Some(Priority::Low),
+                ..Default::default()
+            },
+            CreateTodo {
+                title: "Urgent".to_owned(),
+                priority: Some(Priority::Urgent),
+                ..Default::default()
+            },
+        ])
+        .await
+        .unwrap();
+        let request = Request::get("/todos").body(Body::empty()).unwrap();
+        let (status, body) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let todos: Vec<Todo> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(todos[0].title, "Urgent");
+        assert_eq!(todos[1].title, "Low");
+        // explicit override
+        let request = Request::get("/todos?sort=id").body(Body::empty()).unwrap();
+        let (_, body) = send_raw(repo.clone(), request).await;
+        let todos: Vec<Todo> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(todos[0].title, "Low");
+        let request = Request::get("/todos?sort=bogus")
+            .body(Body::empty())
+            .unwrap();
+        let (status, error) = send_error(repo, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+        assert!(error.message.contains("bogus"));
+    }
+
+    #[tokio::test]
+    async fn test_update_modified() {
+        let repo = create_repo().await;
+        let (status, todo) = patch(
+            repo.clone(),
+            "/todos/1",
+            serde_json::json!({"title": "Changed"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(todo.unwrap().title, "Changed");
+    }
+
+    #[tokio::test]
+    async fn test_update_not_modified() {
+        let repo = create_repo().await;
+        let (status, body) = send_raw(
+            repo.clone(),
+            json_request(
+                "PATCH",
+                "/todos/1",
+                serde_json::json!({"title": "Test todo 1", "completed": false}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_completed() {
+        let repo = create_repo().await;
+        let (status, _) = post(repo.clone(), "/todos/1/complete").await;
+        assert_eq!(status, StatusCode::OK);
+        let request = Request::delete("/todos/completed")
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send_raw(repo, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["deleted"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_pretty_json() {
+        let repo = create_repo().await;
+        let request = Request::get("/todos/1").body(Body::empty()).unwrap();
+        let (_, body) = send_raw(repo.clone(), request).await;
+        assert!(!body.contains(&b'\n'));
+        let request = Request::get("/todos/1?pretty=1")
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send_raw(repo, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.contains("\n  \"title\": \"Test todo 1\""));
+        let todo: Todo = serde_json::from_str(&body).unwrap();
+        assert_eq!(todo.id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_limit_too_large() {
+        let repo = create_repo().await;
+        let uri = format!("/todos?limit={}", MAX_PAGE_LIMIT + 1);
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let (status, error) = send_error(repo, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+        assert!(error.message.contains("at most"));
+    }
+
+    #[tokio::test]
+    async fn test_list_limit_zero() {
+        let repo = create_repo().await;
+        let request = Request::get("/todos?limit=0").body(Body::empty()).unwrap();
+        let (status, error) = send_error(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+        let request = Request::get("/todos?limit=-1").body(Body::empty()).unwrap();
+        let (status, error) = send_error(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+        let request = Request::get("/todos?offset=10")
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send_raw(repo, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let todos: Vec<Todo> = serde_json::from_slice(&body).unwrap();
+        assert!(todos.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_invalid_query() {
+        let repo = create_repo().await;
+        for uri in [
+            "/todos?limit=abc",
+            "/todos?offset=5000000000",
+            "/todos?limit=5000&offset=x",
+            "/todos/changes?since=2024-01-01T00:00:00&limit=abc",
+        ] {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            let (status, error) = send_error(repo.clone(), request).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            assert_eq!(error.code, ErrorCode::Validation, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_ids() {
+        let repo = TodoRepository::in_memory_with(&[
+            CreateTodo {
+                title: "Test todo 1".to_owned(),
+                ..Default::default()
+            },
+            CreateTodo {
+                title: "Test todo 2".to_owned(),
+                ..Default::default()
+            },
+            CreateTodo {
+                title: "Test todo 3".to_owned(),
+                ..Default::default()
+            },
+        ])
+        .await
+        .unwrap();
+        let request = Request::get("/todos?ids=3,1").body(Body::empty()).unwrap();
+        let (status, body) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let todos: Vec<Todo> = serde_json::from_slice(&body).unwrap();
+        let ids: Vec<i64> = todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        let request = Request::get("/todos?ids=1,x").body(Body::empty()).unwrap();
+        let (status, error) = send_error(repo, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+        assert!(error.message.contains("invalid todo id `x`"));
+    }
+
+    #[tokio::test]
+    async fn test_response_time_header() {
+        let repo = create_repo().await;
+        let response = router(repo)
+            .oneshot(Request::get("/todos").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let value = response.headers()[timing::X_RESPONSE_TIME_MS]
+            .to_str()
+            .unwrap();
+        let millis: f64 = value.parse().unwrap();
+        assert!(millis >= 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_list_fields() {
+        let mut repo = create_repo().await;
+        repo.create(CreateTodo {
+            title: "Test todo 2".to_owned(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let request = Request::get("/todos?fields=title,completed&sort=id")
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let todos: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            todos,
+            serde_json::json!([
+                { "title": "Test todo 1", "completed": false },
+                { "title": "Test todo 2", "completed": false },
+            ])
+        );
+        let request = Request::get("/todos?fields=id,title,id&limit=1")
+            .body(Body::empty())
+            .unwrap();
+        let (_, body) = send_raw(repo, request).await;
+        let todos: Vec<serde_json::Map<String, serde_json::Value>> =
+            serde_json::from_slice(&body).unwrap();
+        let keys: Vec<&String> = todos[0].keys().collect();
+        assert_eq!(keys, ["id", "title"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_fields_unknown() {
+        let repo = create_repo().await;
+        let request = Request::get("/todos?fields=id,secret")
+            .body(Body::empty())
+            .unwrap();
+        let (status, error) = send_error(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+        assert!(error.message.contains("unknown field `secret`"));
+        let request = Request::get("/todos?fields=title&ids=1")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send_error(repo, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_set_notes() {
+        let repo = create_repo().await;
+        let (status, todo) = patch(
+            repo.clone(),
+            "/todos/1/notes",
+            serde_json::json!({"notes": "Buy the organic one"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let todo = todo.unwrap();
+        assert_eq!(todo.notes, "Buy the organic one");
+        assert_eq!(todo.title, "Test todo 1");
+        let (status, _) = patch(
+            repo,
+            "/todos/42/notes",
+            serde_json::json!({"notes": "Buy the organic one"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_reopen_all() {
+        let repo = create_repo().await;
+        let (status, _) = post(repo.clone(), "/todos/1/complete").await;
+        assert_eq!(status, StatusCode::OK);
+        let request = Request::post("/todos/reopen").body(Body::empty()).unwrap();
+        let (status, body) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({ "reopened": 1 }));
+        let mut repo = repo;
+        assert!(!repo.get(1).await.unwrap().completed);
+    }
+
+    #[tokio::test]
+    async fn test_complete_matching() {
+        let repo = TodoRepository::in_memory_with(&[
+            CreateTodo {
+                title: "Buy groceries".to_owned(),
+                ..Default::default()
+            },
+            CreateTodo {
+                title: "Walk the dog".to_owned(),
+                ..Default::default()
+            },
+        ])
+        .await
+        .unwrap();
+        let request = Request::builder()
+            .method("POST")
+            .uri("/todos/complete?q=grocer")
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({ "completed": 1 }));
+        let mut repo = repo;
+        assert!(repo.get(1).await.unwrap().completed);
+        assert!(!repo.get(2).await.unwrap().completed);
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/todos/complete?q=")
+            .body(Body::empty())
+            .unwrap();
+        let (status, error) = send_error(repo, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+    }
+
+    #[tokio::test]
+    async fn test_list_not_modified() {
+        let mut repo = TodoRepository::in_memory().await.unwrap();
+        let since = "Tue, 05 Mar 2024 08:30:00 GMT";
+        let (status, _) = send_raw(
+            repo.clone(),
+            Request::get("/todos")
+                .header(IF_MODIFIED_SINCE, since)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        // nothing stored yet, so nothing to compare against
+        assert_eq!(status, StatusCode::OK);
+        let updated_at =
+            NaiveDateTime::parse_from_str("2024-03-05 08:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        repo.upsert(Todo::fixture(1, updated_at)).await.unwrap();
+        let response = router(repo.clone())
+            .oneshot(Request::get("/todos").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[LAST_MODIFIED], since);
+        let (status, body) = send_raw(
+            repo.clone(),
+            Request::get("/todos")
+                .header(IF_MODIFIED_SINCE, since)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(body.is_empty());
+        let response = router(repo.clone())
+            .oneshot(
+                Request::get("/todos")
+                    .header(IF_MODIFIED_SINCE, since)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[LAST_MODIFIED], since);
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-cache");
+        // invalid input is reported before the cache check
+        for uri in [
+            "/todos?limit=0",
+            "/todos?limit=100000",
+            "/todos?fields=id&ids=1",
+            "/todos?min_id=5&max_id=1",
+        ] {
+            let request = Request::get(uri)
+                .header(IF_MODIFIED_SINCE, since)
+                .body(Body::empty())
+                .unwrap();
+            let (status, error) = send_error(repo.clone(), request).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            assert_eq!(error.code, ErrorCode::Validation, "{uri}");
+        }
+        let request = Request::get("/todos?fields=id")
+            .header(IF_MODIFIED_SINCE, since)
+            .header(ACCEPT, "text/csv")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send_error(repo, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_list_modified_after_delete() {
+        let mut repo = TodoRepository::in_memory().await.unwrap();
+        let since = "Tue, 05 Mar 2024 08:30:00 GMT";
+        let updated_at =
+            NaiveDateTime::parse_from_str("2024-03-05 08:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        repo.upsert(Todo::fixture(1, updated_at)).await.unwrap();
+        let request = Request::delete("/todos/1").body(Body::empty()).unwrap();
+        let (status, _) = send_raw(repo.clone(), request).await;
+        assert!(status.is_success());
+        let request = Request::get("/todos")
+            .header(IF_MODIFIED_SINCE, since)
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send_raw(repo, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let todos: Vec<Todo> = serde_json::from_slice(&body).unwrap();
+        assert!(todos.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_modified_after_insert() {
+        let repo = create_repo().await;
+        let since = "Tue, 05 Mar 2024 08:30:00 GMT";
+        let request = Request::get("/todos")
+            .header(IF_MODIFIED_SINCE, since)
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send_raw(repo, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let todos: Vec<Todo> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(todos.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_csv() {
+        let repo = create_repo().await;
+        let response = router(repo)
+            .oneshot(
+                Request::get("/todos")
+                    .header(ACCEPT, "text/csv")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/csv; charset=utf-8");
+        assert_eq!(
+            response.headers()[CONTENT_DISPOSITION],
+            "attachment; filename=\"todos.csv\""
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let mut lines = body.lines();
+        assert!(lines.next().unwrap().starts_with("id,title,notes,"));
+        assert!(lines.next().unwrap().starts_with("1,Test todo 1,"));
+        assert_eq!(lines.next(), None);
+    }
+
+    #[tokio::test]
+    async fn test_list_json_by_default() {
+        let repo = create_repo().await;
+        let response = router(repo)
+            .oneshot(
+                Request::get("/todos")
+                    .header(ACCEPT, "application/json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let todos: Vec<Todo> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(todos[0].title, "Test todo 1");
+    }
+
+    #[tokio::test]
+    async fn test_todo_events() {
+        let mut repo = create_repo().await;
+        let response = router(repo.clone())
+            .oneshot(Request::get("/todos/events").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+        let id = repo
+            .create(CreateTodo {
+                title: "Buy milk".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let mut body = response.into_body().into_data_stream();
+        let frame = tokio::time::timeout(Duration::from_secs(1), body.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let expected = format!("data: {{\"kind\":\"created\",\"id\":{id}}}\n\n");
+        assert_eq!(std::str::from_utf8(&frame).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let repo = create_repo().await;
+        let router = router(repo).layer(middleware::from_fn_with_state(
+            rate_limit::RateLimiter::new(2),
+            rate_limit::rate_limit,
+        ));
+        let get = |uri: &str| {
+            let mut request = Request::get(uri).body(Body::empty()).unwrap();
+            request.extensions_mut().insert(axum::extract::ConnectInfo(
+                std::net::SocketAddr::from(([10, 0, 0, 1], 4000)),
+            ));
+            request
+        };
+        for _ in 0..2 {
+            let response = router.clone().oneshot(get("/todos/1")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = router.clone().oneshot(get("/todos/1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[axum::http::header::RETRY_AFTER], "1");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: ErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.code, ErrorCode::RateLimited);
+        let response = router.oneshot(get("/health")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_set_order() {
+        let mut repo = create_repo().await;
+        for title in ["Test todo 2", "Test todo 3"] {
+            repo.create(CreateTodo {
+                title: title.to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        }
+        let request = json_request(
+            "PUT",
+            "/todos/order",
+            serde_json::json!({ "order": [3, 1, 2] }),
+        );
+        let (status, _) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        for (id, position) in [(3, 0), (1, 1), (2, 2)] {
+            assert_eq!(repo.get(id).await.unwrap().position, position);
+        }
+        let request = json_request(
+            "PUT",
+            "/todos/order",
+            serde_json::json!({ "order": [3, 1] }),
+        );
+        let (status, error) = send_error(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+        assert!(error.message.contains("missing 2"));
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        let mut repo = create_repo().await;
+        repo.create(CreateTodo {
+            title: "Test todo 2".to_owned(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let request = json_request(
+            "POST",
+            "/todos/batch",
+            serde_json::json!({ "operations": [
+                { "create": { "title": "Batch todo" } },
+                { "update": { "id": 1, "completed": true } },
+                { "delete": 2 },
+            ] }),
+        );
+        let (status, body) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let outcomes: Vec<BatchOutcome> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(outcomes.len(), 3);
+        assert!(matches!(&outcomes[0], BatchOutcome::Created(todo) if todo.title == "Batch todo"));
+        assert!(matches!(&outcomes[1], BatchOutcome::Updated(todo) if todo.completed));
+        assert_eq!(outcomes[2], BatchOutcome::Deleted(2));
+        assert_eq!(repo.count().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_batch_rolls_back() {
+        let mut repo = create_repo().await;
+        let operations = serde_json::json!([
+            { "create": { "title": "Batch todo" } },
+            { "update": { "id": 1, "title": "Renamed" } },
+            { "delete": 42 },
+        ]);
+        let request = json_request(
+            "POST",
+            "/todos/batch",
+            serde_json::json!({ "operations": operations }),
+        );
+        let (status, error) = send_error(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+        assert!(error.message.contains("operation 2"));
+        assert_eq!(repo.count().await.unwrap(), 1);
+        assert_ne!(repo.get(1).await.unwrap().title, "Renamed");
+
+        let request = json_request(
+            "POST",
+            "/todos/batch",
+            serde_json::json!({ "operations": operations, "continue_on_error": true }),
+        );
+        let (status, body) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let outcomes: Vec<BatchOutcome> = serde_json::from_slice(&body).unwrap();
+        assert!(matches!(outcomes[2], BatchOutcome::Error(_)));
+        assert_eq!(repo.count().await.unwrap(), 2);
+        assert_eq!(repo.get(1).await.unwrap().title, "Renamed");
+    }
+
+    #[tokio::test]
+    async fn test_todo_schema() {
+        let repo = create_repo().await;
+        let request = Request::get("/schema/todo").body(Body::empty()).unwrap();
+        let (status, body) = send_raw(repo, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let schemas: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let names = |value: &serde_json::Value| -> Vec<String> {
+            let mut names: Vec<String> = match value {
+                serde_json::Value::Object(map) => map.keys().cloned().collect(),
+                serde_json::Value::Array(items) => items
+                    .iter()
+                    .map(|item| item.as_str().unwrap().to_owned())
+                    .collect(),
+                _ => Vec::new(),
+            };
+            names.sort();
+            names
+        };
+
+        let todo = &schemas["todo"];
+        assert_eq!(todo["title"], "Todo");
+        let properties = names(&todo["properties"]);
+        for field in ["id", "title", "notes", "completed", "priority", "due_date"] {
+            assert!(properties.contains(&field.to_owned()), "todo lacks {field}");
+        }
+        let required = names(&todo["required"]);
+        assert!(required.contains(&"title".to_owned()));
+        assert!(!required.contains(&"due_date".to_owned()));
+
+        let create = &schemas["create_todo"];
+        assert_eq!(names(&create["required"]), vec!["title"]);
+        assert!(names(&create["properties"]).contains(&"priority".to_owned()));
+        let update = &schemas["update_todo"];
+        assert!(names(&update["required"]).is_empty());
+        assert_eq!(
+            names(&update["properties"]),
+            vec!["completed", "notes", "priority", "title"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_todo_stats() {
+        let repo = create_repo().await;
+        let request = Request::get("/todos/stats").body(Body::empty()).unwrap();
+        let (status, body) = send_raw(repo, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let stats: TodoStats = serde_json::from_slice(&body).unwrap();
+        assert_eq!((stats.total, stats.open, stats.completed), (1, 1, 0));
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        for field in ["pool_size", "idle", "num_connections"] {
+            assert!(body["pool"][field].is_u64(), "{field} is not a number");
+        }
+        let pool: PoolMetrics = serde_json::from_value(body["pool"].clone()).unwrap();
+        assert!(pool.num_connections >= 1);
+        assert!(pool.num_connections <= pool.pool_size);
+    }
+
+    #[tokio::test]
+    async fn test_security_headers() {
+        let repo = create_repo().await;
+        let response = router(repo.clone())
+            .oneshot(Request::get("/todos").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(response.headers()[X_FRAME_OPTIONS], "DENY");
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-cache");
+        let response = router(repo)
+            .oneshot(Request::get("/todos/42").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+    }
+
+    #[tokio::test]
+    async fn test_get_todo_by_title() {
+        let mut repo = create_repo().await;
+        let id = repo
+            .create(CreateTodo {
+                title: "Read w/ friends".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let request = Request::get("/todos/by-title/Test%20todo%201")
+            .body(Body::empty())
+            .unwrap();
+        let (status, todo) = send(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(todo.unwrap().id, 1);
+
+        let request = Request::get("/todos/by-title/Read%20w%2F%20friends")
+            .body(Body::empty())
+            .unwrap();
+        let (status, todo) = send(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(todo.unwrap().id, id);
+
+        let request = Request::get("/todos/by-title/Test%20todo%202")
+            .body(Body::empty())
+            .unwrap();
+        let (status, error) = send_error(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error.code, ErrorCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_get_todo_expand() {
+        let mut repo = create_repo().await;
+        let child = repo
+            .create(CreateTodo {
+                title: "Test todo 2".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        repo.move_todo(child, Some(Some(1)), None).await.unwrap();
+        repo.add_tag_many(&[1], "work").await.unwrap();
+
+        let request = Request::get("/todos/1").body(Body::empty()).unwrap();
+        let (status, plain) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let plain: serde_json::Value = serde_json::from_slice(&plain).unwrap();
+        assert_eq!(
+            plain,
+            serde_json::to_value(repo.get(1).await.unwrap()).unwrap()
+        );
+
+        let request = Request::get("/todos/1?expand=tags,children")
+            .body(Body::empty())
+            .unwrap();
+        let (status, expanded) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut expanded: serde_json::Value = serde_json::from_slice(&expanded).unwrap();
+        assert_eq!(expanded["tags"], serde_json::json!(["work"]));
+        assert_eq!(
+            expanded["children"],
+            serde_json::to_value(repo.children(1).await.unwrap()).unwrap()
+        );
+        let object = expanded.as_object_mut().unwrap();
+        object.remove("tags");
+        object.remove("children");
+        assert_eq!(expanded, plain);
+
+        let request = Request::get("/todos/1?expand=owner")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send_error(repo, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_changes() {
+        let mut repo = create_repo().await;
+        let mut old = repo.get(1).await.unwrap();
+        old.id = 5;
+        old.created_at =
+            NaiveDateTime::parse_from_str("2024-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        repo.upsert(old).await.unwrap();
+        repo.set_notes(5, "edited").await.unwrap();
+        let ids = |body: Vec<u8>| {
+            let todos: Vec<Todo> = serde_json::from_slice(&body).unwrap();
+            todos.iter().map(|todo| todo.id).collect::<Vec<i64>>()
+        };
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+        let since = "since=2024-06-01T00:00:00";
+        let (status, body) = send_raw(repo.clone(), get(&format!("/todos/changes?{since}"))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids(body).len(), 2);
+        let uri = format!("/todos/changes?{since}&kind=created");
+        let (_, body) = send_raw(repo.clone(), get(&uri)).await;
+        assert_eq!(ids(body), vec![1]);
+        let uri = format!("/todos/changes?{since}&kind=updated");
+        let (_, body) = send_raw(repo.clone(), get(&uri)).await;
+        assert_eq!(ids(body), vec![5]);
+        let (status, _) = send_error(repo.clone(), get("/todos/changes?kind=created")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let uri = format!("/todos/changes?{since}&kind=deleted");
+        let (status, _) = send_error(repo, get(&uri)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_list_id_range() {
+        let mut repo = create_repo().await;
+        for id in 2..=4 {
+            let _ = repo
+                .create(CreateTodo {
+                    title: format!("Test todo {id}"),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+        let ids = |body: Vec<u8>| {
+            let todos: Vec<Todo> = serde_json::from_slice(&body).unwrap();
+            todos.iter().map(|todo| todo.id).collect::<Vec<i64>>()
+        };
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+        let (status, body) = send_raw(repo.clone(), get("/todos?min_id=2&max_id=3")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids(body), vec![2, 3]);
+        let (_, body) = send_raw(repo.clone(), get("/todos?min_id=3")).await;
+        assert_eq!(ids(body), vec![3, 4]);
+        let (_, body) = send_raw(repo.clone(), get("/todos?max_id=1")).await;
+        assert_eq!(ids(body), vec![1]);
+        let (status, _) = send_error(repo, get("/todos?min_id=3&max_id=2")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_random_todo() {
+        let repo = create_repo().await;
+        let request = Request::get("/todos/random").body(Body::empty()).unwrap();
+        let (status, todo) = send(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let todo = todo.unwrap();
+        assert_eq!(todo.id, 1);
+        assert!(!todo.completed);
+        let (status, _) = post(repo.clone(), "/todos/1/complete").await;
+        assert_eq!(status, StatusCode::OK);
+        let request = Request::get("/todos/random").body(Body::empty()).unwrap();
+        let (status, error) = send_error(repo, request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error.code, ErrorCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_set_priority_many() {
+        let repo = create_repo().await;
+        let request = json_request(
+            "PATCH",
+            "/todos/priority",
+            serde_json::json!({"ids": [1, 2], "priority": "urgent"}),
+        );
+        let (status, body) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({ "updated": 1 }));
+        let mut repo = repo;
+        assert_eq!(repo.get(1).await.unwrap().priority, Priority::Urgent);
+    }
+
+    #[tokio::test]
+    async fn test_tag_many() {
+        let mut repo = create_repo().await;
+        let _ = repo
+            .create(CreateTodo {
+                title: "Test todo 2".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let request = json_request(
+            "PATCH",
+            "/todos/tags",
+            serde_json::json!({"ids": [1, 2], "add": "work"}),
+        );
+        let (status, body) = send_raw(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({ "added": 2, "removed": 0 }));
+        assert_eq!(repo.list_by_tag("work").await.unwrap().len(), 2);
+
+        let request = json_request(
+            "PATCH",
+            "/todos/tags",
+            serde_json::json!({"ids": [2], "add": "home", "remove": "work"}),
+        );
+        let (_, body) = send_raw(repo.clone(), request).await;
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({ "added": 1, "removed": 1 }));
+
+        let request = json_request("PATCH", "/todos/tags", serde_json::json!({"ids": [1]}));
+        let (status, error) = send_error(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, ErrorCode::Validation);
+
+        let request = json_request(
+            "PATCH",
+            "/todos/tags",
+            serde_json::json!({"ids": [1], "add": "urgent", "remove": " "}),
+        );
+        let (status, _) = send_error(repo.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(repo.list_by_tag("urgent").await.unwrap().is_empty());
+    }
+}
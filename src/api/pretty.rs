@@ -0,0 +1,64 @@
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+/// Whether `PRETTY_JSON` asks for pretty responses by default
+pub fn pretty_from_env() -> bool {
+    std::env::var("PRETTY_JSON").is_ok_and(|value| is_truthy(&value))
+}
+
+fn is_truthy(value: &str) -> bool {
+    matches!(value, "1" | "true" | "yes")
+}
+
+/// Whether the query string contains `pretty=1`
+fn wants_pretty(query: Option<&str>) -> bool {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .any(|(key, value)| key == "pretty" && is_truthy(value))
+}
+
+/// Re-render JSON response bodies with indentation
+///
+/// `axum::Json` always writes compact JSON, so pretty output is produced here
+/// after the handler ran. Compact responses pass through untouched.
+pub async fn pretty_json(State(always): State<bool>, request: Request, next: Next) -> Response {
+    let pretty = always || wants_pretty(request.uri().query());
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if !pretty || !is_json {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let body = serde_json::from_slice::<serde_json::Value>(&bytes)
+        .and_then(|value| serde_json::to_vec_pretty(&value))
+        .unwrap_or_else(|_| bytes.to_vec());
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_wants_pretty() {
+        assert!(wants_pretty(Some("pretty=1")));
+        assert!(wants_pretty(Some("offset=1&pretty=true")));
+        assert!(!wants_pretty(Some("pretty=0")));
+        assert!(!wants_pretty(Some("offset=1")));
+        assert!(!wants_pretty(None));
+    }
+}
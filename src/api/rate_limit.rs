@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use super::error::ApiError;
+
+/// Requests per second and client unless `RATE_LIMIT_RPS` says otherwise
+pub const DEFAULT_RATE_LIMIT_RPS: u32 = 100;
+
+/// Clients tracked before buckets that refilled completely are forgotten
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Requests per second from `RATE_LIMIT_RPS`, `0` turns rate limiting off
+pub fn rate_limit_from_env() -> u32 {
+    rate_limit_from(std::env::var("RATE_LIMIT_RPS").ok())
+}
+
+fn rate_limit_from(var: Option<String>) -> u32 {
+    match var.as_deref().map(str::trim) {
+        None | Some("") => DEFAULT_RATE_LIMIT_RPS,
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            tracing::warn!(
+                "RATE_LIMIT_RPS={value} is not a number, using {DEFAULT_RATE_LIMIT_RPS}"
+            );
+            DEFAULT_RATE_LIMIT_RPS
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Token bucket per client ip, allowing bursts of up to one second worth of requests
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    rps: u32,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(rps: u32) -> RateLimiter {
+        RateLimiter {
+            rps,
+            buckets: Arc::default(),
+        }
+    }
+
+    /// Take a token for `ip`, or the seconds to wait until one is available
+    fn acquire(&self, ip: IpAddr, now: Instant) -> Result<(), u64> {
+        if self.rps == 0 {
+            return Ok(());
+        }
+        let rps = f64::from(self.rps);
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.refilled_at).as_secs_f64() * rps < rps
+            });
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: rps,
+            refilled_at: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rps).min(rps);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / rps).ceil().max(1.0) as u64)
+        }
+    }
+}
+
+/// Answer 429 with `Retry-After` to clients over the limit, `/health` is never limited
+///
+/// Clients are told apart by their address from [`ConnectInfo`], requests without
+/// one share a single bucket.
+pub async fn rate_limit(
+    State(limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.uri().path() == "/health" {
+        return next.run(request).await;
+    }
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |info| info.0.ip());
+    match limiter.acquire(ip, Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => ApiError::RateLimited { retry_after }.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_rate_limit_from() {
+        assert_eq!(rate_limit_from(None), DEFAULT_RATE_LIMIT_RPS);
+        assert_eq!(rate_limit_from(Some("5".to_owned())), 5);
+        assert_eq!(rate_limit_from(Some("0".to_owned())), 0);
+        assert_eq!(
+            rate_limit_from(Some("fast".to_owned())),
+            DEFAULT_RATE_LIMIT_RPS
+        );
+    }
+
+    #[test]
+    fn test_acquire() {
+        let limiter = RateLimiter::new(2);
+        let now = Instant::now();
+        let (first, second) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+        assert_eq!(limiter.acquire(first, now), Ok(()));
+        assert_eq!(limiter.acquire(first, now), Ok(()));
+        assert_eq!(limiter.acquire(first, now), Err(1));
+        assert_eq!(limiter.acquire(second, now), Ok(()));
+        let later = now + Duration::from_millis(500);
+        assert_eq!(limiter.acquire(first, later), Ok(()));
+        assert_eq!(limiter.acquire(first, later), Err(1));
+        assert!(RateLimiter::new(0).acquire(first, now).is_ok());
+    }
+}
@@ -0,0 +1,85 @@
+use std::future::Future;
+use std::time::Duration;
+
+/// Time the server gets to drain unless `SHUTDOWN_TIMEOUT_SECS` says otherwise
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Drain timeout from `SHUTDOWN_TIMEOUT_SECS`
+pub fn shutdown_timeout() -> Duration {
+    shutdown_timeout_from(std::env::var("SHUTDOWN_TIMEOUT_SECS").ok())
+}
+
+fn shutdown_timeout_from(var: Option<String>) -> Duration {
+    match var.as_deref().map(str::trim) {
+        None | Some("") => DEFAULT_SHUTDOWN_TIMEOUT,
+        Some(value) => value.parse().map(Duration::from_secs).unwrap_or_else(|_| {
+            tracing::warn!(
+                "SHUTDOWN_TIMEOUT_SECS={value} is not a number, using {}",
+                DEFAULT_SHUTDOWN_TIMEOUT.as_secs()
+            );
+            DEFAULT_SHUTDOWN_TIMEOUT
+        }),
+    }
+}
+
+/// Run `server`, giving it at most `timeout` to finish once `shutdown` resolved
+///
+/// Returns `None` when the server was still draining after the timeout, the caller
+/// is expected to exit anyway.
+pub async fn drain<T>(
+    server: impl Future<Output = T>,
+    shutdown: impl Future<Output = ()>,
+    timeout: Duration,
+) -> Option<T> {
+    tokio::pin!(server);
+    tokio::select! {
+        output = &mut server => return Some(output),
+        () = shutdown => {}
+    }
+    match tokio::time::timeout(timeout, server).await {
+        Ok(output) => Some(output),
+        Err(_) => {
+            tracing::warn!("server did not shut down within {timeout:?}, forcing exit");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_times_out() {
+        let server = std::future::pending::<()>();
+        let output = drain(server, async {}, Duration::from_millis(10)).await;
+        assert_eq!(output, None);
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_server() {
+        let server = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            "done"
+        };
+        let output = drain(server, async {}, Duration::from_secs(5)).await;
+        assert_eq!(output, Some("done"));
+        // the server may also stop on its own before any signal
+        let output = drain(async { 1 }, std::future::pending(), Duration::ZERO).await;
+        assert_eq!(output, Some(1));
+    }
+
+    #[test]
+    fn test_shutdown_timeout_from() {
+        assert_eq!(shutdown_timeout_from(None), DEFAULT_SHUTDOWN_TIMEOUT);
+        assert_eq!(
+            shutdown_timeout_from(Some("soon".to_owned())),
+            DEFAULT_SHUTDOWN_TIMEOUT
+        );
+        assert_eq!(
+            shutdown_timeout_from(Some("5".to_owned())),
+            Duration::from_secs(5)
+        );
+    }
+}
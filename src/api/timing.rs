@@ -0,0 +1,23 @@
+use std::time::Instant;
+
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+
+/// Milliseconds the server spent on a request, e.g. `X-Response-Time-Ms: 1.250`
+pub const X_RESPONSE_TIME_MS: HeaderName = HeaderName::from_static("x-response-time-ms");
+
+/// Set [`X_RESPONSE_TIME_MS`] on every response
+///
+/// The clock stops once the response headers are ready, streamed bodies like
+/// the event feed only count until their first byte.
+pub async fn response_time(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let mut response = next.run(request).await;
+    let millis = start.elapsed().as_secs_f64() * 1000.0;
+    if let Ok(value) = HeaderValue::from_str(&format!("{millis:.3}")) {
+        response.headers_mut().insert(X_RESPONSE_TIME_MS, value);
+    }
+    response
+}
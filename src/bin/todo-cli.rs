@@ -1,13 +1,42 @@
 use anyhow::Result;
 use clap::{Parser};
-use todo::cli::Args;
+use todo::cli::{error_json, Args};
+use todo::command::Command;
+use todo::db;
+use todo::todo::TodoRepository;
 
 
-fn main( ) -> Result<()> {
+#[tokio::main]
+async fn main( ) -> Result<()> {
 
 	let cli = Args::parse();
 
-	cli.command.run()?;
+	tracing_subscriber::fmt()
+		.with_max_level(cli.level_filter())
+		.with_writer(std::io::stderr)
+		.init();
+
+	if let Err(err) = run(&cli).await {
+		if cli.json {
+			eprintln!("{}", error_json(&err));
+			std::process::exit(1);
+		}
+		return Err(err);
+	}
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+async fn run(cli: &Args) -> Result<()> {
+	let pool = db::connect(&db::database_url()?).await?;
+	// the migrate command reports what it applies itself
+	if !matches!(cli.command, Command::Migrate(_)) {
+		db::migrate(&pool).await?;
+		db::check_schema(&pool).await?;
+	}
+	let mut repo = TodoRepository::new(pool);
+	let mut stdout = std::io::stdout().lock();
+	let result = cli.run(&mut repo, &mut stdout).await;
+	repo.close().await;
+	result
+}
@@ -1,13 +1,878 @@
+use std::fmt::Display;
+use std::io::Write;
+
+use anyhow::Result;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{NaiveDateTime, Utc};
+use clap::{ArgAction, Parser, ValueEnum};
+use serde::Serialize;
+use terminal_size::Width;
+use tracing::level_filters::LevelFilter;
 
-use clap::{Parser, Subcommand};
 use crate::command::Command;
+use crate::todo::{Todo, TodoRepository};
 
+/// Name of the CLI binary, used for generated shell completions
+pub const BIN_NAME: &str = "todo-cli";
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
+    /// Print results as JSON for scripting
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// Don't print confirmations, errors and JSON output are still printed
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+    /// Show more logs, repeat for more detail (-v info, -vv debug, -vvv trace)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
     /// Command to execute on destination host
     #[command(subcommand)]
     pub command: Command,
 }
 
+impl Args {
+    /// Log level selected by the `-v` flags
+    pub fn level_filter(&self) -> LevelFilter {
+        level_filter(self.verbose)
+    }
+
+    pub async fn run(&self, repo: &mut TodoRepository, writer: &mut dyn Write) -> Result<()> {
+        let mut out = Output::new(self.json, writer).quiet(self.quiet);
+        self.command.run(repo, &mut out).await
+    }
+}
+
+/// Map the number of `-v` flags to a log level, quiet by default
+pub fn level_filter(verbose: u8) -> LevelFilter {
+    match verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+/// Destination of command results, human readable or JSON
+pub struct Output<'a> {
+    json: bool,
+    quiet: bool,
+    writer: &'a mut dyn Write,
+}
+
+impl<'a> Output<'a> {
+    pub fn new(json: bool, writer: &'a mut dyn Write) -> Output<'a> {
+        Output {
+            json,
+            quiet: false,
+            writer,
+        }
+    }
+
+    /// Skip the text of [`Output::confirm`]
+    pub fn quiet(mut self, quiet: bool) -> Output<'a> {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Write `value` as JSON in JSON mode, `text` otherwise
+    pub fn emit<T: Serialize>(&mut self, value: &T, text: impl Display) -> Result<()> {
+        if self.json {
+            self.emit_json(value)?;
+        } else {
+            writeln!(self.writer, "{text}")?;
+        }
+        Ok(())
+    }
+
+    /// Write `value` as JSON whether or not JSON mode is on
+    pub fn emit_json<T: Serialize>(&mut self, value: &T) -> Result<()> {
+        serde_json::to_writer(&mut *self.writer, value)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    /// Write `value` as a YAML document whether or not JSON mode is on
+    pub fn emit_yaml<T: Serialize>(&mut self, value: &T) -> Result<()> {
+        serde_yaml::to_writer(&mut *self.writer, value)?;
+        Ok(())
+    }
+
+    /// Like [`Output::emit`] for confirmations of a change, the text is dropped in quiet mode
+    pub fn confirm<T: Serialize>(&mut self, value: &T, text: impl Display) -> Result<()> {
+        if self.quiet && !self.json {
+            return Ok(());
+        }
+        self.emit(value, text)
+    }
+
+    /// Underlying writer for output that is neither JSON nor a line of text
+    pub fn writer(&mut self) -> &mut dyn Write {
+        self.writer
+    }
+}
+
+/// Layouts of commands printing todos
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TodoFormat {
+    /// One `[x] #3 Buy milk` line per todo
+    Lines,
+    /// Aligned columns fitting the terminal, Unicode markers unless --plain
+    Table,
+    /// JSON with full values, like --json
+    Json,
+    /// YAML with full values
+    Yaml,
+}
+
+/// Table width when stdout is not a terminal
+pub const DEFAULT_TABLE_WIDTH: usize = 80;
+
+/// Title column width left when the terminal is too narrow for the other columns
+const MIN_TITLE_WIDTH: usize = 10;
+
+/// Columns of the terminal stdout is attached to, [`DEFAULT_TABLE_WIDTH`] when it's not a tty
+pub fn table_width() -> usize {
+    terminal_size::terminal_size()
+        .map(|(Width(width), _)| usize::from(width))
+        .unwrap_or(DEFAULT_TABLE_WIDTH)
+}
+
+/// Characters tables are drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// `✓` for done todos and `…` for cut titles
+    Unicode,
+    /// `[x]`/`[ ]` markers and `...`, safe for any terminal or log
+    Ascii,
+}
+
+impl Charset {
+    /// Unicode on a terminal, ASCII when stdout is redirected or `plain` asks for it
+    pub fn detect(plain: bool) -> Charset {
+        if plain || terminal_size::terminal_size().is_none() {
+            Charset::Ascii
+        } else {
+            Charset::Unicode
+        }
+    }
+
+    fn ellipsis(self) -> &'static str {
+        match self {
+            Charset::Unicode => "…",
+            Charset::Ascii => "...",
+        }
+    }
+
+    fn marker(self, completed: bool) -> &'static str {
+        match (self, completed) {
+            (Charset::Unicode, true) => "✓",
+            (Charset::Unicode, false) => "",
+            (Charset::Ascii, true) => "[x]",
+            (Charset::Ascii, false) => "[ ]",
+        }
+    }
+}
+
+/// Shorten `value` to at most `width` characters, ending in `ellipsis` when something was cut
+pub fn truncate(value: &str, width: usize, ellipsis: &str) -> String {
+    if value.chars().count() <= width {
+        return value.to_owned();
+    }
+    let ellipsis_len = ellipsis.chars().count();
+    if width < ellipsis_len {
+        return ellipsis.chars().take(width).collect();
+    }
+    let mut truncated: String = value.chars().take(width - ellipsis_len).collect();
+    truncated.push_str(ellipsis);
+    truncated
+}
+
+/// How timestamps are printed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// Distance to now like `2h ago`, see [`relative_time`]
+    Relative,
+    /// `2024-03-05T08:30:00`
+    Iso,
+    /// Any `strftime` pattern like `%d.%m.%Y %H:%M`
+    Strftime(String),
+}
+
+impl TimeFormat {
+    /// Render `time` as of `now`, both in UTC
+    pub fn format(&self, time: NaiveDateTime, now: NaiveDateTime) -> String {
+        match self {
+            TimeFormat::Relative => relative_time(time, now),
+            TimeFormat::Iso => time.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            TimeFormat::Strftime(pattern) => time.format(pattern).to_string(),
+        }
+    }
+}
+
+/// Parse `relative`, `iso` or a `strftime` pattern, rejecting patterns chrono can't render
+pub fn parse_time_format(value: &str) -> Result<TimeFormat, String> {
+    match value {
+        "relative" => Ok(TimeFormat::Relative),
+        "iso" => Ok(TimeFormat::Iso),
+        pattern if StrftimeItems::new(pattern).any(|item| item == Item::Error) => Err(format!(
+            "invalid time format `{pattern}`, expected relative, iso or a strftime pattern"
+        )),
+        pattern => Ok(TimeFormat::Strftime(pattern.to_owned())),
+    }
+}
+
+/// Coarse distance between `time` and `now` like `just now`, `5m ago`, `3d ago` or `in 2h`
+///
+/// Anything at least 30 days away is printed as its date instead.
+pub fn relative_time(time: NaiveDateTime, now: NaiveDateTime) -> String {
+    let delta = now.signed_duration_since(time);
+    let seconds = delta.num_seconds().abs();
+    let amount = match seconds {
+        0..=59 => return "just now".to_owned(),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86_399 => format!("{}h", seconds / 3600),
+        86_400..=2_591_999 => format!("{}d", seconds / 86_400),
+        _ => return time.format("%Y-%m-%d").to_string(),
+    };
+    if delta.num_seconds() < 0 {
+        format!("in {amount}")
+    } else {
+        format!("{amount} ago")
+    }
+}
+
+/// Render `todos` as a table at most `width` columns wide, cutting titles to fit
+pub fn todo_table(
+    todos: &[Todo],
+    width: usize,
+    charset: Charset,
+    time_format: &TimeFormat,
+) -> String {
+    let now = Utc::now().naive_utc();
+    let updated: Vec<String> = todos
+        .iter()
+        .map(|todo| time_format.format(todo.updated_at, now))
+        .collect();
+    let id_width = todos
+        .iter()
+        .map(|todo| todo.id.to_string().len())
+        .chain(["ID".len()])
+        .max()
+        .unwrap_or_default();
+    let updated_width = updated
+        .iter()
+        .map(|updated| updated.chars().count())
+        .chain(["UPDATED".len()])
+        .max()
+        .unwrap_or_default();
+    // id, done, priority and updated columns, each followed by two spaces
+    let fixed = id_width + "DONE".len() + "PRIORITY".len() + updated_width + 4 * 2;
+    let title_width = width.saturating_sub(fixed).max(MIN_TITLE_WIDTH);
+    let row = |id: &dyn Display, done: &str, priority: &str, updated: &str, title: &str| {
+        format!(
+            "{id:>id_width$}  {done:<4}  {priority:<8}  {updated:<updated_width$}  {}",
+            truncate(title, title_width, charset.ellipsis())
+        )
+    };
+    std::iter::once(row(&"ID", "DONE", "PRIORITY", "UPDATED", "TITLE"))
+        .chain(todos.iter().zip(&updated).map(|(todo, updated)| {
+            let done = charset.marker(todo.completed);
+            // `Priority` ignores padding, so pad its name instead
+            row(
+                &todo.id,
+                done,
+                &todo.priority.to_string(),
+                updated,
+                &todo.title,
+            )
+        }))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Render an error as the JSON object printed to stderr in JSON mode
+pub fn error_json(err: &anyhow::Error) -> String {
+    serde_json::json!({ "error": format!("{err:#}") }).to_string()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::todo::CreateTodo;
+
+    async fn run(repo: &mut TodoRepository, args: &[&str]) -> Result<String> {
+        let args = Args::try_parse_from(std::iter::once("todo-cli").chain(args.iter().copied()))?;
+        let mut buf = Vec::new();
+        args.run(repo, &mut buf).await?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    async fn create_repo() -> TodoRepository {
+        TodoRepository::in_memory_with(&[CreateTodo {
+            title: "Test todo 1".to_owned(),
+            ..Default::default()
+        }])
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_new() {
+        let mut repo = create_repo().await;
+        let text = run(&mut repo, &["new", "Buy", "milk"]).await.unwrap();
+        assert_eq!(text, "created todo 2\n");
+        let json = run(&mut repo, &["new", "--json", "Buy", "bread"])
+            .await
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["id"], 3);
+        assert_eq!(repo.get(2).await.unwrap().title, "Buy milk");
+    }
+
+    #[tokio::test]
+    async fn test_new_notes() {
+        let mut repo = create_repo().await;
+        let text = run(
+            &mut repo,
+            &["new", "Buy", "milk", "--notes", "the organic one"],
+        )
+        .await
+        .unwrap();
+        assert_eq!(text, "created todo 2\n");
+        let todo = repo.get(2).await.unwrap();
+        assert_eq!(todo.title, "Buy milk");
+        assert_eq!(todo.notes, "the organic one");
+        let _ = run(&mut repo, &["new", "Buy", "bread"]).await.unwrap();
+        assert_eq!(repo.get(3).await.unwrap().notes, "");
+    }
+
+    #[tokio::test]
+    async fn test_new_quiet() {
+        let mut repo = create_repo().await;
+        let text = run(&mut repo, &["new", "--quiet", "Buy", "milk"])
+            .await
+            .unwrap();
+        assert_eq!(text, "");
+        assert_eq!(repo.get(2).await.unwrap().title, "Buy milk");
+        let json = run(&mut repo, &["-q", "--json", "new", "Buy", "bread"])
+            .await
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["id"], 3);
+        // data is not a confirmation
+        let text = run(&mut repo, &["-q", "get", "1"]).await.unwrap();
+        assert_eq!(text, "[ ] #1 Test todo 1\n");
+        assert!(run(&mut repo, &["-q", "get", "42"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get() {
+        let mut repo = create_repo().await;
+        let text = run(&mut repo, &["get", "1"]).await.unwrap();
+        assert_eq!(text, "[ ] #1 Test todo 1\n");
+        let json = run(&mut repo, &["--json", "get", "1"]).await.unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["title"], "Test todo 1");
+        assert_eq!(value["completed"], false);
+    }
+
+    #[tokio::test]
+    async fn test_get_format() {
+        let mut repo = create_repo().await;
+        let yaml = run(&mut repo, &["get", "1", "--format", "yaml"])
+            .await
+            .unwrap();
+        assert!(yaml.contains("title: Test todo 1\n"));
+        let todo: Todo = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(todo, repo.get(1).await.unwrap());
+        let json = run(&mut repo, &["get", "1", "--format", "json"])
+            .await
+            .unwrap();
+        let todo: Todo = serde_json::from_str(&json).unwrap();
+        assert_eq!(todo.title, "Test todo 1");
+        let text = run(&mut repo, &["get", "1", "--format", "table"])
+            .await
+            .unwrap();
+        assert_eq!(
+            text,
+            "ID  DONE  PRIORITY  UPDATED   TITLE\n 1  [ ]   normal    just now  Test todo 1\n"
+        );
+        let yaml = run(&mut repo, &["list", "--format", "yaml"]).await.unwrap();
+        let todos: Vec<Todo> = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(todos, vec![todo]);
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let mut repo = create_repo().await;
+        let json = run(&mut repo, &["--json", "delete", "1"]).await.unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["deleted"], 1);
+        let text = run(&mut repo, &["delete", "1"]).await.unwrap();
+        assert_eq!(text, "deleted 0 todo(s)\n");
+    }
+
+    #[tokio::test]
+    async fn test_error_json() {
+        let mut repo = create_repo().await;
+        let err = run(&mut repo, &["--json", "get", "42"]).await.unwrap_err();
+        let value: serde_json::Value = serde_json::from_str(&error_json(&err)).unwrap();
+        assert!(value["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_seed() {
+        let mut repo = TodoRepository::in_memory().await.unwrap();
+        let text = run(&mut repo, &["seed", "--count", "7"]).await.unwrap();
+        assert_eq!(text, "created 7 sample todo(s)\n");
+        assert_eq!(repo.count().await.unwrap(), 7);
+        // refuse to seed a non-empty table unless forced
+        assert!(run(&mut repo, &["seed", "--count", "3"]).await.is_err());
+        run(&mut repo, &["seed", "--count", "3", "--force"])
+            .await
+            .unwrap();
+        assert_eq!(repo.count().await.unwrap(), 10);
+    }
+
+    #[test]
+    fn test_level_filter() {
+        assert_eq!(level_filter(0), LevelFilter::WARN);
+        assert_eq!(level_filter(1), LevelFilter::INFO);
+        assert_eq!(level_filter(2), LevelFilter::DEBUG);
+        assert_eq!(level_filter(3), LevelFilter::TRACE);
+        assert_eq!(level_filter(7), LevelFilter::TRACE);
+        let args = Args::try_parse_from(["todo-cli", "-vv", "list"]).unwrap();
+        assert_eq!(args.level_filter(), LevelFilter::DEBUG);
+        let args = Args::try_parse_from(["todo-cli", "list", "-v"]).unwrap();
+        assert_eq!(args.level_filter(), LevelFilter::INFO);
+    }
+
+    #[tokio::test]
+    async fn test_list_offset_too_large() {
+        let mut repo = create_repo().await;
+        let too_large = (u64::from(u32::MAX) + 1).to_string();
+        let err = run(&mut repo, &["list", &too_large]).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("offset {too_large} is too large, at most {}", u32::MAX)
+        );
+        let err = run(&mut repo, &["list", "0", &too_large])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("limit "));
+    }
+
+    #[tokio::test]
+    async fn test_list_since_until() {
+        let mut repo = create_repo().await;
+        let text = run(&mut repo, &["list", "--since", "2024-01-01"])
+            .await
+            .unwrap();
+        assert_eq!(text, "[ ] #1 Test todo 1\n");
+        let text = run(&mut repo, &["list", "--until", "2024-01-01"])
+            .await
+            .unwrap();
+        assert_eq!(text, "\n");
+        let err = run(&mut repo, &["list", "--since", "01/02/2024"])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("expected YYYY-MM-DD"));
+    }
+
+    #[tokio::test]
+    async fn test_list_count() {
+        let mut repo = create_repo().await;
+        let _ = run(&mut repo, &["new", "Buy", "milk"]).await.unwrap();
+        let text = run(&mut repo, &["list", "--count"]).await.unwrap();
+        assert_eq!(text, "2\n");
+        let text = run(&mut repo, &["list", "--count", "--since", "2024-01-01"])
+            .await
+            .unwrap();
+        assert_eq!(text, "2\n");
+        let text = run(&mut repo, &["list", "--count", "--until", "2024-01-01"])
+            .await
+            .unwrap();
+        assert_eq!(text, "0\n");
+        let json = run(&mut repo, &["--json", "list", "--count"])
+            .await
+            .unwrap();
+        assert_eq!(json, "2\n");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Buy milk", 20, "…"), "Buy milk");
+        assert_eq!(truncate("Buy milk", 8, "…"), "Buy milk");
+        assert_eq!(truncate("Buy milk and bread", 8, "…"), "Buy mil…");
+        assert_eq!(truncate("Äpfel über alles", 6, "…"), "Äpfel…");
+        assert_eq!(truncate("Buy milk", 1, "…"), "…");
+        assert_eq!(truncate("Buy milk", 0, "…"), "");
+        assert_eq!(truncate("Buy milk and bread", 8, "..."), "Buy m...");
+        assert_eq!(truncate("Buy milk", 2, "..."), "..");
+    }
+
+    #[tokio::test]
+    async fn test_list_table() {
+        let mut repo = create_repo().await;
+        let title = ["A very long title"; 10].join(" ");
+        let _ = run(&mut repo, &["new", &title]).await.unwrap();
+        let _ = run(&mut repo, &["update", "1", "--completed", "true"])
+            .await
+            .unwrap();
+        // tests don't write to a terminal, so the table is ASCII already
+        let text = run(&mut repo, &["list", "--format", "table"])
+            .await
+            .unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "ID  DONE  PRIORITY  UPDATED   TITLE");
+        assert_eq!(lines[1], " 1  [x]   normal    just now  Test todo 1");
+        assert_eq!(lines[2].chars().count(), DEFAULT_TABLE_WIDTH);
+        assert!(lines[2].ends_with("..."));
+        let json = run(&mut repo, &["list", "--format", "json"]).await.unwrap();
+        let todos: Vec<Todo> = serde_json::from_str(&json).unwrap();
+        assert_eq!(todos[1].title, title);
+
+        let unicode = todo_table(&todos, 42, Charset::Unicode, &TimeFormat::Relative);
+        let lines: Vec<&str> = unicode.lines().collect();
+        assert_eq!(lines[1], " 1  ✓     normal    just now  Test todo 1");
+        assert_eq!(lines[2], " 2        normal    just now  A very long…");
+    }
+
+    #[test]
+    fn test_relative_time() {
+        let now =
+            NaiveDateTime::parse_from_str("2024-03-10 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let ago = |seconds: i64| relative_time(now - chrono::Duration::seconds(seconds), now);
+        assert_eq!(ago(0), "just now");
+        assert_eq!(ago(59), "just now");
+        assert_eq!(ago(60), "1m ago");
+        assert_eq!(ago(45 * 60), "45m ago");
+        assert_eq!(ago(2 * 3600 + 59 * 60), "2h ago");
+        assert_eq!(ago(3 * 86_400), "3d ago");
+        assert_eq!(ago(-2 * 3600), "in 2h");
+        assert_eq!(ago(40 * 86_400), "2024-01-30");
+    }
+
+    #[tokio::test]
+    async fn test_time_format() {
+        let mut repo = create_repo().await;
+        let updated_at = repo.get(1).await.unwrap().updated_at;
+        let text = run(
+            &mut repo,
+            &["get", "1", "--format", "table", "--time-format", "iso"],
+        )
+        .await
+        .unwrap();
+        let iso = updated_at.format("%Y-%m-%dT%H:%M:%S").to_string();
+        assert!(text.lines().nth(1).unwrap().contains(&iso));
+        let text = run(
+            &mut repo,
+            &["list", "--format", "table", "--time-format", "%d.%m.%Y"],
+        )
+        .await
+        .unwrap();
+        let day = updated_at.format("%d.%m.%Y").to_string();
+        assert!(text.lines().nth(1).unwrap().contains(&day));
+        let err = run(&mut repo, &["list", "--time-format", "%Q"])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid time format `%Q`"));
+    }
+
+    #[tokio::test]
+    async fn test_list_plain() {
+        let mut repo = create_repo().await;
+        let _ = run(&mut repo, &["new", "Buy", "milk"]).await.unwrap();
+        let _ = run(&mut repo, &["update", "1", "--completed", "true"])
+            .await
+            .unwrap();
+        let text = run(&mut repo, &["list", "--plain"]).await.unwrap();
+        assert_eq!(text, "[x] #1 Test todo 1\n[ ] #2 Buy milk\n");
+        let text = run(&mut repo, &["list", "--plain", "--format", "table"])
+            .await
+            .unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[1], " 1  [x]   normal    just now  Test todo 1");
+        assert_eq!(lines[2], " 2  [ ]   normal    just now  Buy milk");
+        assert!(text.is_ascii());
+    }
+
+    #[tokio::test]
+    async fn test_list_reverse() {
+        let mut repo = create_repo().await;
+        for title in ["Walk dog", "buy milk"] {
+            let _ = run(&mut repo, &["new", title]).await.unwrap();
+        }
+        let ids = |text: String| -> Vec<i64> {
+            let todos: Vec<Todo> = serde_json::from_str(&text).unwrap();
+            todos.iter().map(|todo| todo.id).collect()
+        };
+        let text = run(&mut repo, &["--json", "list"]).await.unwrap();
+        assert_eq!(ids(text), vec![1, 2, 3]);
+        let text = run(&mut repo, &["--json", "list", "--reverse"])
+            .await
+            .unwrap();
+        assert_eq!(ids(text), vec![3, 2, 1]);
+        let text = run(&mut repo, &["--json", "list", "--sort", "title", "-r"])
+            .await
+            .unwrap();
+        assert_eq!(ids(text), vec![2, 1, 3]);
+        // reversed pages are cut from the reversed list
+        let text = run(&mut repo, &["--json", "list", "-r", "0", "2"])
+            .await
+            .unwrap();
+        assert_eq!(ids(text), vec![3, 2]);
+        assert!(run(&mut repo, &["list", "-r", "--since", "2024-01-01"])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_move() {
+        let mut repo = create_repo().await;
+        let _ = run(&mut repo, &["new", "Buy", "milk"]).await.unwrap();
+        let json = run(&mut repo, &["--json", "move", "2", "--to-parent", "1"])
+            .await
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["parent_id"], 1);
+        let err = run(&mut repo, &["move", "1", "--to-parent", "2"])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("cycle"));
+        let _ = run(&mut repo, &["move", "2", "--to-root", "--position", "4"])
+            .await
+            .unwrap();
+        let todo = repo.get(2).await.unwrap();
+        assert_eq!((todo.parent_id, todo.position), (None, 4));
+        assert!(run(&mut repo, &["move", "2"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let mut repo = create_repo().await;
+        let _ = run(&mut repo, &["new", "Buy", "milk"]).await.unwrap();
+        let _ = run(&mut repo, &["update", "2", "--completed", "true"])
+            .await
+            .unwrap();
+        let text = run(&mut repo, &["stats"]).await.unwrap();
+        assert_eq!(
+            text,
+            "total:     2\nopen:      1\ncompleted: 1\noverdue:   0\npriority:  urgent 0, high 0, normal 2, low 0\n"
+        );
+        let json = run(&mut repo, &["--json", "stats"]).await.unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["open"], 1);
+        assert_eq!(value["by_priority"]["normal"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_prune() {
+        let mut repo = create_repo().await;
+        let now = chrono::Utc::now().naive_utc();
+        for (id, days_ago) in [(2, 40), (3, 5)] {
+            let mut todo = repo.get(1).await.unwrap();
+            todo.id = id;
+            todo.completed = true;
+            todo.completed_at = Some(now - chrono::Duration::days(days_ago));
+            repo.upsert(todo).await.unwrap();
+        }
+        let text = run(&mut repo, &["prune", "--older-than", "30d"])
+            .await
+            .unwrap();
+        assert_eq!(text, "deleted 1 todo(s)\n");
+        let ids: Vec<i64> = repo
+            .list(crate::pagination::Pagination::default())
+            .await
+            .unwrap()
+            .iter()
+            .map(|todo| todo.id)
+            .collect();
+        assert_eq!(ids, vec![1, 3]);
+        let json = run(&mut repo, &["--json", "prune", "--older-than", "1w"])
+            .await
+            .unwrap();
+        assert_eq!(json, "{\"deleted\":0}\n");
+        for age in ["30", "d", "-1d", "3é", "10y"] {
+            assert!(run(&mut repo, &["prune", "--older-than", age])
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_completions() {
+        let mut repo = create_repo().await;
+        let script = run(&mut repo, &["completions", "bash"]).await.unwrap();
+        assert!(!script.is_empty());
+        assert!(script.contains(BIN_NAME));
+        assert!(run(&mut repo, &["completions", "tcsh"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_to_file() {
+        let mut repo = create_repo().await;
+        let path = std::env::temp_dir().join(format!("todo-export-{}.jsonl", std::process::id()));
+        let text = run(
+            &mut repo,
+            &[
+                "export",
+                "--format",
+                "jsonl",
+                "--output",
+                path.to_str().unwrap(),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(text, format!("exported 1 todo(s) to {}\n", path.display()));
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let todo: crate::todo::Todo = serde_json::from_str(content.trim()).unwrap();
+        assert_eq!(todo.title, "Test todo 1");
+
+        let missing = std::env::temp_dir().join("no-such-dir").join("todos.csv");
+        let err = run(&mut repo, &["export", "-o", missing.to_str().unwrap()])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot open"));
+    }
+
+    #[tokio::test]
+    async fn test_export_no_header() {
+        let mut repo = create_repo().await;
+        let csv = run(&mut repo, &["export", "--format", "csv"])
+            .await
+            .unwrap();
+        assert!(csv.starts_with("id,title,"));
+        let csv = run(&mut repo, &["export", "--format", "csv", "--no-header"])
+            .await
+            .unwrap();
+        let mut lines = csv.lines();
+        assert!(lines.next().unwrap().starts_with("1,Test todo 1,"));
+        assert_eq!(lines.next(), None);
+    }
+
+    #[tokio::test]
+    async fn test_import() {
+        let mut repo = create_repo().await;
+        let dir = std::env::temp_dir().join(format!("todo-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [
+            ("todos.csv", "title,completed\nFrom CSV,true\n"),
+            ("todos.json", r#"[{"title": "From JSON"}]"#),
+            (
+                "todos.jsonl",
+                "{\"title\": \"From JSONL\"}\n{\"notes\": \"no title\"}\n",
+            ),
+        ];
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+        let text = run(&mut repo, &["import", &path("todos.csv")])
+            .await
+            .unwrap();
+        assert_eq!(text, "imported 1 todo(s), skipped 0\n");
+        let json = run(&mut repo, &["--json", "import", &path("todos.json")])
+            .await
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["imported"], 1);
+        let text = run(&mut repo, &["import", &path("todos.jsonl")])
+            .await
+            .unwrap();
+        assert!(text.starts_with("imported 1 todo(s), skipped 1\n"));
+        assert!(text.contains("todos.jsonl:2: "));
+        let titles: Vec<String> = repo
+            .list_sorted(
+                crate::pagination::Pagination::default(),
+                crate::sort::SortBy::Id,
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|todo| todo.title)
+            .collect();
+        assert_eq!(
+            titles,
+            vec!["Test todo 1", "From CSV", "From JSON", "From JSONL"]
+        );
+
+        let err = run(
+            &mut repo,
+            &["import", "--format", "json", &path("todos.csv")],
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("invalid JSON"));
+        std::fs::write(dir.join("todos.txt"), "").unwrap();
+        let err = run(&mut repo, &["import", &path("todos.txt")])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("pass --format"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_export_max_rows() {
+        let mut repo = create_repo().await;
+        let _ = run(&mut repo, &["new", "Buy", "milk"]).await.unwrap();
+        let err = run(&mut repo, &["export", "--max-rows", "1"])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--force"));
+        let json = run(&mut repo, &["export", "--max-rows", "1", "--force"])
+            .await
+            .unwrap();
+        let todos: Vec<crate::todo::Todo> = serde_json::from_str(&json).unwrap();
+        assert_eq!(todos.len(), 2);
+        assert!(run(&mut repo, &["export", "--max-rows", "2"]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_migrate() {
+        let pool = crate::db::connect("sqlite::memory:").await.unwrap();
+        let mut repo = TodoRepository::new(pool.clone());
+        let json = run(&mut repo, &["migrate", "--dry-run", "--json"])
+            .await
+            .unwrap();
+        let pending: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        assert_eq!(pending[0]["version"], 20240315193733_i64);
+        assert_eq!(pending[0]["description"], "init");
+        let tracked: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = '_sqlx_migrations')",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert!(!tracked);
+
+        let text = run(&mut repo, &["migrate"]).await.unwrap();
+        assert_eq!(text.lines().count(), pending.len());
+        assert!(text.starts_with("applied 20240315193733 init\n"));
+        let versions: Vec<i64> = sqlx::query_scalar(
+            "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let expected: Vec<i64> = pending
+            .iter()
+            .map(|migration| migration["version"].as_i64().unwrap())
+            .collect();
+        assert_eq!(versions, expected);
+        repo.create(CreateTodo {
+            title: "Migrated".to_owned(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let text = run(&mut repo, &["migrate", "--dry-run"]).await.unwrap();
+        assert_eq!(text, "no pending migrations\n");
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::{bail, Result};
+
+use clap::{Parser};
+use serde_json::json;
+
+use crate::cli::Output;
+use crate::todo::TodoRepository;
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+	/// Only delete completed todos
+	#[arg(long)]
+	completed: bool,
+	/// Delete every todo, completed or not
+	#[arg(long)]
+	all: bool,
+}
+
+
+impl Cli {
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		let deleted = match (self.completed, self.all) {
+			(true, false) => repo.delete_completed().await?,
+			(false, true) => repo.cleanup().await?,
+			_ => bail!("pass either --completed or --all"),
+		};
+		out.confirm(&json!({ "deleted": deleted }), format!("deleted {deleted} todo(s)"))
+	}
+}
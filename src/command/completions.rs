@@ -0,0 +1,22 @@
+use anyhow::Result;
+
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+
+use crate::cli::{Args, Output, BIN_NAME};
+use crate::todo::TodoRepository;
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+	/// Shell to generate the completion script for
+	#[arg(value_enum)]
+	shell: Shell,
+}
+
+
+impl Cli {
+	pub async fn run(&self, _repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		clap_complete::generate(self.shell, &mut Args::command(), BIN_NAME, out.writer());
+		Ok(())
+	}
+}
@@ -0,0 +1,21 @@
+use anyhow::Result;
+
+use clap::{Parser};
+use serde_json::json;
+
+use crate::cli::Output;
+use crate::todo::TodoRepository;
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+	/// Todo id
+	id: i64,
+}
+
+
+impl Cli {
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		let deleted = repo.delete(self.id).await?;
+		out.confirm(&json!({ "deleted": deleted }), format!("deleted {deleted} todo(s)"))
+	}
+}
@@ -0,0 +1,59 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+
+use clap::{Parser};
+use serde_json::json;
+
+use crate::cli::Output;
+use crate::todo::{ExportFormat, TodoRepository};
+
+/// Exports above this many todos log a warning
+const LARGE_EXPORT_ROWS: i64 = 100_000;
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+	/// File format
+	#[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+	format: ExportFormat,
+	/// File to write, stdout when missing
+	#[arg(long, short)]
+	output: Option<PathBuf>,
+	/// Refuse to export when the table has more todos than this
+	#[arg(long)]
+	max_rows: Option<i64>,
+	/// Export even when the table exceeds --max-rows
+	#[arg(long)]
+	force: bool,
+	/// Leave out the CSV header row, e.g. to append to an existing file
+	#[arg(long)]
+	no_header: bool,
+}
+
+
+impl Cli {
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		let count = repo.count().await?;
+		if let Some(max_rows) = self.max_rows {
+			if count > max_rows && !self.force {
+				bail!("table has {count} todos, more than --max-rows {max_rows}; use --force to export anyway");
+			}
+		}
+		if count > LARGE_EXPORT_ROWS {
+			tracing::warn!("exporting {count} todos, this may take a while and a lot of space");
+		}
+		let Some(path) = &self.output else {
+			repo.export(self.format, !self.no_header, out.writer()).await?;
+			return Ok(());
+		};
+		let file = File::create(path)
+			.with_context(|| format!("cannot open {} for writing", path.display()))?;
+		let exported = repo.export(self.format, !self.no_header, BufWriter::new(file)).await?;
+		out.confirm(
+			&json!({ "exported": exported, "output": path }),
+			format!("exported {exported} todo(s) to {}", path.display()),
+		)
+	}
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+
+use clap::{Parser};
+
+use crate::cli::{self, Charset, Output, TimeFormat, TodoFormat};
+use crate::todo::TodoRepository;
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+	/// Todo id
+	id: i64,
+	/// How to print the todo
+	#[arg(long, value_enum, default_value_t = TodoFormat::Lines)]
+	format: TodoFormat,
+	/// How the table prints times: relative, iso or a strftime pattern like %d.%m.%Y
+	#[arg(long, value_parser = cli::parse_time_format, default_value = "relative")]
+	time_format: TimeFormat,
+}
+
+
+impl Cli {
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		let todo = repo.get(self.id).await?;
+		match self.format {
+			TodoFormat::Lines => out.emit(&todo, &todo),
+			TodoFormat::Table => {
+				let width = cli::table_width();
+				let todos = std::slice::from_ref(&todo);
+				let table = cli::todo_table(todos, width, Charset::detect(false), &self.time_format);
+				out.emit(&todo, table)
+			}
+			TodoFormat::Json => out.emit_json(&todo),
+			TodoFormat::Yaml => out.emit_yaml(&todo),
+		}
+	}
+}
@@ -0,0 +1,52 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+
+use clap::{Parser};
+
+use crate::cli::Output;
+use crate::todo::{ExportFormat, TodoRepository};
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+	/// File to import
+	path: PathBuf,
+	/// File format, guessed from the extension when missing
+	#[arg(long, value_enum)]
+	format: Option<ExportFormat>,
+}
+
+/// Format matching the extension of `path`, e.g. `todos.csv`
+fn format_of(path: &Path) -> Option<ExportFormat> {
+	let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+	match extension.as_str() {
+		"csv" => Some(ExportFormat::Csv),
+		"json" => Some(ExportFormat::Json),
+		"jsonl" | "ndjson" => Some(ExportFormat::Jsonl),
+		_ => None,
+	}
+}
+
+
+impl Cli {
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		let format = self
+			.format
+			.or_else(|| format_of(&self.path))
+			.ok_or_else(|| anyhow!("cannot tell the format of {}, pass --format", self.path.display()))?;
+		let file = File::open(&self.path)
+			.with_context(|| format!("cannot open {} for reading", self.path.display()))?;
+		let report = repo.import(format, BufReader::new(file)).await?;
+		let mut text = format!("imported {} todo(s), skipped {}", report.imported, report.skipped);
+		for (line, err) in &report.errors {
+			text.push_str(&format!("\n{}:{line}: {err}", self.path.display()));
+		}
+		if report.errors.is_empty() {
+			out.confirm(&report, text)
+		} else {
+			out.emit(&report, text)
+		}
+	}
+}
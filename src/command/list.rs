@@ -1,19 +1,98 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
+use chrono::NaiveDate;
 use clap::{Parser};
 
+use crate::cli::{self, Charset, Output, TimeFormat, TodoFormat};
+use crate::pagination::Pagination;
+use crate::sort::SortBy;
+use crate::todo::TodoRepository;
+
 #[derive(Debug, Parser)]
 pub struct Cli{
 	/// Offset
  	offset:Option<usize>,
 	/// Limit
 	limit: Option<usize>,
+	/// Only todos created on or after this day, e.g. 2024-01-01
+	#[arg(long, value_parser = parse_date)]
+	since: Option<NaiveDate>,
+	/// Only todos created before this day, e.g. 2024-02-01
+	#[arg(long, value_parser = parse_date)]
+	until: Option<NaiveDate>,
+	/// Order of the todos, ranges from --since/--until are always in creation order
+	#[arg(long, value_enum, default_value_t = SortBy::default(), conflicts_with_all = ["since", "until"])]
+	sort: SortBy,
+	/// Flip the order, e.g. newest first with --sort id
+	#[arg(short, long, conflicts_with_all = ["since", "until"])]
+	reverse: bool,
+	/// How to print the todos, `table` cuts titles to the terminal width
+	#[arg(long, value_enum, default_value_t = TodoFormat::Lines)]
+	format: TodoFormat,
+	/// ASCII only, `[x]`/`[ ]` markers and `...` even on a terminal
+	#[arg(long)]
+	plain: bool,
+	/// How the table prints times: relative, iso or a strftime pattern like %d.%m.%Y
+	#[arg(long, value_parser = cli::parse_time_format, default_value = "relative")]
+	time_format: TimeFormat,
+	/// Only print how many todos match --since/--until, ignores offset and limit
+	#[arg(long, conflicts_with_all = ["sort", "reverse", "format", "plain"])]
+	count: bool,
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+	NaiveDate::parse_from_str(value, "%Y-%m-%d")
+		.map_err(|err| format!("invalid date `{value}`, expected YYYY-MM-DD: {err}"))
+}
+
+/// Convert a CLI count to what `Pagination` stores, instead of silently truncating it
+fn to_u32(name: &str, value: Option<usize>) -> Result<Option<u32>> {
+	value
+		.map(|value| {
+			u32::try_from(value)
+				.map_err(|_| anyhow!("{name} {value} is too large, at most {}", u32::MAX))
+		})
+		.transpose()
 }
 
 
 impl Cli {
-	pub fn run(&self) -> Result<()> {
-		println!("show full list of todo's");
-		Ok(())
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		let pagination = Pagination::new(
+			to_u32("offset", self.offset)?,
+			to_u32("limit", self.limit)?,
+		);
+		let start_of = |day: NaiveDate| day.and_hms_opt(0, 0, 0);
+		if self.count {
+			let count = repo
+				.count_created_between(self.since.and_then(start_of), self.until.and_then(start_of))
+				.await?;
+			return out.emit(&count, count.to_string());
+		}
+		let todos = if self.since.is_some() || self.until.is_some() {
+			repo
+				.list_created_between(
+					self.since.and_then(start_of),
+					self.until.and_then(start_of),
+					pagination,
+				)
+				.await?
+		} else {
+			repo.list_ordered(pagination, self.sort, self.reverse).await?
+		};
+		let text = match self.format {
+			TodoFormat::Json => return out.emit_json(&todos),
+			TodoFormat::Yaml => return out.emit_yaml(&todos),
+			TodoFormat::Table => {
+				let charset = Charset::detect(self.plain);
+				cli::todo_table(&todos, cli::table_width(), charset, &self.time_format)
+			}
+			TodoFormat::Lines => todos
+				.iter()
+				.map(|todo| todo.to_line())
+				.collect::<Vec<_>>()
+				.join("\n"),
+		};
+		out.emit(&todos, text)
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+
+use clap::{Parser};
+
+use crate::cli::Output;
+use crate::db;
+use crate::todo::TodoRepository;
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+	/// List pending migrations without applying them
+	#[arg(long)]
+	dry_run: bool,
+}
+
+
+impl Cli {
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		if self.dry_run {
+			let pending = db::pending_migrations(repo.pool()).await?;
+			let text = lines("pending", &pending);
+			return out.emit(&pending, text);
+		}
+		let applied = db::migrate(repo.pool()).await?;
+		let text = lines("applied", &applied);
+		out.confirm(&applied, text)
+	}
+}
+
+/// One `<verb> <version> <description>` line per migration
+fn lines(verb: &str, migrations: &[db::MigrationInfo]) -> String {
+	if migrations.is_empty() {
+		return "no pending migrations".to_owned();
+	}
+	migrations.iter().map(|migration| format!("{verb} {migration}")).collect::<Vec<_>>().join("\n")
+}
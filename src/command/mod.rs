@@ -1,10 +1,23 @@
-use std::ops::Neg;
-
 use anyhow::Result;
 use clap::Subcommand;
 
+use crate::cli::Output;
+use crate::todo::TodoRepository;
+
+pub mod clear;
+pub mod completions;
+pub mod delete;
+pub mod export;
+pub mod get;
+pub mod import;
 pub mod list;
+pub mod migrate;
+pub mod r#move;
 pub mod new;
+pub mod prune;
+pub mod seed;
+pub mod stats;
+pub mod update;
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
@@ -12,13 +25,49 @@ pub enum Command {
     New(new::Cli),
 	/// Show todo list
     List(list::Cli),
+	/// Show single todo
+	Get(get::Cli),
+	/// Change title, notes or completion of a todo
+	Update(update::Cli),
+	/// Delete todo
+	Delete(delete::Cli),
+	/// Move todo under another one or to a new position
+	Move(r#move::Cli),
+	/// Delete completed or all todos
+	Clear(clear::Cli),
+	/// Delete todos completed a while ago
+	Prune(prune::Cli),
+	/// Fill the database with sample todos
+	Seed(seed::Cli),
+	/// Count todos by completion and priority
+	Stats(stats::Cli),
+	/// Write all todos as CSV, JSON or JSON lines
+	Export(export::Cli),
+	/// Create todos from a CSV, JSON or JSON lines file
+	Import(import::Cli),
+	/// Apply pending database migrations
+	Migrate(migrate::Cli),
+	/// Print a shell completion script
+	Completions(completions::Cli),
 }
 
 impl Command {
-    pub fn run(&self) -> Result<()> {
+    pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
 		match self {
-			Command::New(cli) => cli.run()?,
-			Command::List(cli) => cli.run()?,
+			Command::New(cli) => cli.run(repo, out).await?,
+			Command::List(cli) => cli.run(repo, out).await?,
+			Command::Get(cli) => cli.run(repo, out).await?,
+			Command::Update(cli) => cli.run(repo, out).await?,
+			Command::Delete(cli) => cli.run(repo, out).await?,
+			Command::Move(cli) => cli.run(repo, out).await?,
+			Command::Clear(cli) => cli.run(repo, out).await?,
+			Command::Prune(cli) => cli.run(repo, out).await?,
+			Command::Seed(cli) => cli.run(repo, out).await?,
+			Command::Stats(cli) => cli.run(repo, out).await?,
+			Command::Export(cli) => cli.run(repo, out).await?,
+			Command::Import(cli) => cli.run(repo, out).await?,
+			Command::Migrate(cli) => cli.run(repo, out).await?,
+			Command::Completions(cli) => cli.run(repo, out).await?,
 		}
 		Ok(())
 	}
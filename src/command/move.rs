@@ -0,0 +1,37 @@
+use anyhow::{bail, Result};
+
+use clap::{Parser};
+
+use crate::cli::Output;
+use crate::todo::TodoRepository;
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+	/// Todo id
+	id: i64,
+	/// Make the todo a subtask of this todo
+	#[arg(long, conflicts_with = "to_root")]
+	to_parent: Option<i64>,
+	/// Make the todo a top level todo again
+	#[arg(long)]
+	to_root: bool,
+	/// New position among its siblings
+	#[arg(long)]
+	position: Option<i64>,
+}
+
+
+impl Cli {
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		let parent = match (self.to_parent, self.to_root) {
+			(Some(parent), _) => Some(Some(parent)),
+			(None, true) => Some(None),
+			(None, false) => None,
+		};
+		if parent.is_none() && self.position.is_none() {
+			bail!("pass --to-parent, --to-root or --position");
+		}
+		let todo = repo.move_todo(self.id, parent, self.position).await?;
+		out.confirm(&todo, &todo)
+	}
+}
@@ -1,17 +1,29 @@
 use anyhow::Result;
 
 use clap::{Parser};
+use serde_json::json;
+
+use crate::cli::Output;
+use crate::todo::{CreateTodo, TodoRepository};
 
 #[derive(Debug, Parser)]
 pub struct Cli{
 	/// New todo title
 	title: Vec<String>,
+	/// Notes of the new todo
+	#[arg(long)]
+	notes: Option<String>,
 }
 
 
 impl Cli {
-	pub fn run(&self) -> Result<()> {
-		println!("create new task {}",self.title.iter().map(|x|x.to_string()).collect::<String>());
-		Ok(())
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		let todo = CreateTodo {
+			title: self.title.join(" "),
+			notes: self.notes.clone().unwrap_or_default(),
+			..Default::default()
+		};
+		let id = repo.create(todo).await?;
+		out.confirm(&json!({ "id": id }), format!("created todo {id}"))
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,40 @@
+use anyhow::Result;
+
+use chrono::{Duration, Utc};
+use clap::{Parser};
+use serde_json::json;
+
+use crate::cli::Output;
+use crate::todo::TodoRepository;
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+	/// Delete completed todos finished longer ago than this, e.g. 30d, 12h or 2w
+	#[arg(long, value_parser = parse_age)]
+	older_than: Duration,
+}
+
+/// Parse an age like `90m`, `12h`, `30d` or `2w`
+fn parse_age(value: &str) -> Result<Duration, String> {
+	let invalid = || format!("invalid age `{value}`, expected a number followed by m, h, d or w");
+	let (unit_at, _) = value.char_indices().last().ok_or_else(invalid)?;
+	let (amount, unit) = value.split_at(unit_at);
+	let amount: i64 = amount.parse().map_err(|_| invalid())?;
+	let age = match unit {
+		"m" => Duration::try_minutes(amount),
+		"h" => Duration::try_hours(amount),
+		"d" => Duration::try_days(amount),
+		"w" => Duration::try_weeks(amount),
+		_ => None,
+	};
+	age.filter(|age| *age >= Duration::zero()).ok_or_else(invalid)
+}
+
+
+impl Cli {
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		let cutoff = Utc::now().naive_utc() - self.older_than;
+		let deleted = repo.delete_completed_before(cutoff).await?;
+		out.confirm(&json!({ "deleted": deleted }), format!("deleted {deleted} todo(s)"))
+	}
+}
@@ -0,0 +1,50 @@
+use anyhow::{bail, Result};
+
+use clap::{Parser};
+use serde_json::json;
+
+use crate::cli::Output;
+use crate::todo::{CreateTodo, Priority, TodoRepository};
+
+const TITLES: [&str; 8] = [
+	"Buy milk",
+	"Call the plumber",
+	"Write the quarterly report",
+	"Book flight tickets",
+	"Renew passport",
+	"Water the plants",
+	"Read a chapter of a book",
+	"Clean the garage",
+];
+
+const PRIORITIES: [Priority; 4] = [Priority::Low, Priority::Normal, Priority::High, Priority::Urgent];
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+	/// Number of sample todos
+	#[arg(long, default_value_t = 10)]
+	count: usize,
+	/// Seed even if the table already has todos
+	#[arg(long)]
+	force: bool,
+}
+
+
+impl Cli {
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		if !self.force && repo.count().await? > 0 {
+			bail!("todos table is not empty, use --force to seed anyway");
+		}
+		let todos = (0..self.count)
+			.map(|n| CreateTodo {
+				title: format!("{} #{}", TITLES[n % TITLES.len()], n + 1),
+				priority: Some(PRIORITIES[n % PRIORITIES.len()]),
+				// every third sample todo is already done
+				completed: n % 3 == 0,
+				..Default::default()
+			})
+			.collect::<Vec<_>>();
+		let ids = repo.create_many(&todos).await?;
+		out.confirm(&json!({ "created": ids.len() }), format!("created {} sample todo(s)", ids.len()))
+	}
+}
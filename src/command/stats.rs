@@ -0,0 +1,18 @@
+use anyhow::Result;
+
+use clap::{Parser};
+
+use crate::cli::Output;
+use crate::todo::TodoRepository;
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+}
+
+
+impl Cli {
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		let stats = repo.stats().await?;
+		out.emit(&stats, &stats)
+	}
+}
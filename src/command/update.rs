@@ -0,0 +1,36 @@
+use anyhow::Result;
+
+use clap::{Parser};
+use serde_json::json;
+
+use crate::cli::Output;
+use crate::todo::{TodoRepository, UpdateTodo};
+
+#[derive(Debug, Parser)]
+pub struct Cli{
+	/// Todo id
+	id: i64,
+	/// New title
+	#[arg(long)]
+	title: Option<String>,
+	/// New notes
+	#[arg(long)]
+	notes: Option<String>,
+	/// Mark todo as done or open
+	#[arg(long)]
+	completed: Option<bool>,
+}
+
+
+impl Cli {
+	pub async fn run(&self, repo: &mut TodoRepository, out: &mut Output<'_>) -> Result<()> {
+		let update = UpdateTodo {
+			title: self.title.clone(),
+			notes: self.notes.clone(),
+			completed: self.completed,
+			..Default::default()
+		};
+		let updated = u64::from(repo.update(self.id, update).await?.is_some());
+		out.confirm(&json!({ "updated": updated }), format!("updated {updated} todo(s)"))
+	}
+}
@@ -0,0 +1,595 @@
+use std::env::VarError;
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous,
+};
+
+use crate::todo::DEFAULT_TABLE;
+
+/// Database used when `DATABASE_URL` is not set, created on first use
+pub const DEFAULT_DATABASE_URL: &str = "sqlite://todos.db?mode=rwc";
+
+/// Database URL from `DATABASE_URL`, falling back to [`DEFAULT_DATABASE_URL`]
+pub fn database_url() -> Result<String> {
+    database_url_from(std::env::var("DATABASE_URL"))
+}
+
+fn database_url_from(var: Result<String, VarError>) -> Result<String> {
+    match var {
+        Ok(url) if url.trim().is_empty() => {
+            bail!("DATABASE_URL is empty; example: DATABASE_URL=sqlite://todos.db")
+        }
+        Ok(url) => Ok(url),
+        Err(VarError::NotPresent) => {
+            tracing::info!("DATABASE_URL is not set, using {DEFAULT_DATABASE_URL}");
+            Ok(DEFAULT_DATABASE_URL.to_owned())
+        }
+        Err(VarError::NotUnicode(_)) => {
+            bail!("DATABASE_URL is not valid unicode; example: DATABASE_URL=sqlite://todos.db")
+        }
+    }
+}
+
+/// Table sqlx records applied migrations in
+pub const MIGRATIONS_TABLE: &str = "_sqlx_migrations";
+
+/// Migration from `migrations/`
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct MigrationInfo {
+    pub version: i64,
+    pub description: String,
+}
+
+impl fmt::Display for MigrationInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.version, self.description)
+    }
+}
+
+/// Migrations from `migrations/` not applied to `pool` yet, oldest first
+///
+/// Only reads the database, a missing [`MIGRATIONS_TABLE`] means nothing was applied.
+pub async fn pending_migrations(pool: &SqlitePool) -> Result<Vec<MigrationInfo>> {
+    let tracked: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+    )
+    .bind(MIGRATIONS_TABLE)
+    .fetch_one(pool)
+    .await?;
+    let applied: Vec<i64> = if tracked {
+        sqlx::query_scalar(&format!(
+            "SELECT version FROM {MIGRATIONS_TABLE} WHERE success"
+        ))
+        .fetch_all(pool)
+        .await?
+    } else {
+        Vec::new()
+    };
+    Ok(sqlx::migrate!()
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| MigrationInfo {
+            version: migration.version,
+            description: migration.description.to_string(),
+        })
+        .collect())
+}
+
+/// Apply pending migrations from `migrations/`, returning the ones applied
+///
+/// Also adds or drops the unique title index as [`unique_titles`] asks.
+pub async fn migrate(pool: &SqlitePool) -> Result<Vec<MigrationInfo>> {
+    let pending = pending_migrations(pool).await?;
+    sqlx::migrate!().run(pool).await?;
+    set_unique_titles(pool, unique_titles()).await?;
+    Ok(pending)
+}
+
+/// Index making titles of [`DEFAULT_TABLE`] unique, see [`set_unique_titles`]
+pub const UNIQUE_TITLE_INDEX: &str = "todos_title_unique";
+
+/// Whether `UNIQUE_TITLES` asks for unique titles, off unless set to `1`, `true` or `yes`
+pub fn unique_titles() -> bool {
+    unique_titles_from(std::env::var("UNIQUE_TITLES").ok())
+}
+
+fn unique_titles_from(var: Option<String>) -> bool {
+    match var.as_deref().map(str::trim) {
+        None | Some("" | "0" | "false" | "no") => false,
+        Some("1" | "true" | "yes") => true,
+        Some(value) => {
+            tracing::warn!("UNIQUE_TITLES={value} is not a boolean, titles stay non-unique");
+            false
+        }
+    }
+}
+
+/// Create or drop [`UNIQUE_TITLE_INDEX`]
+///
+/// With the index plain creates of a taken title fail with a conflict and
+/// `create_unique` skips them even when two connections race. Creating it fails
+/// while duplicate titles exist, naming a few of them.
+pub async fn set_unique_titles(pool: &SqlitePool, unique: bool) -> Result<()> {
+    if !unique {
+        sqlx::query(&format!("DROP INDEX IF EXISTS {UNIQUE_TITLE_INDEX}"))
+            .execute(pool)
+            .await?;
+        return Ok(());
+    }
+    let duplicates: Vec<String> = sqlx::query_scalar(&format!(
+        "SELECT title FROM {DEFAULT_TABLE} GROUP BY title HAVING COUNT(*) > 1 ORDER BY title LIMIT 5"
+    ))
+    .fetch_all(pool)
+    .await?;
+    if !duplicates.is_empty() {
+        bail!(
+            "UNIQUE_TITLES is set but {DEFAULT_TABLE} has duplicate titles, rename or delete them first: {}",
+            duplicates.join(", ")
+        );
+    }
+    sqlx::query(&format!(
+        "CREATE UNIQUE INDEX IF NOT EXISTS {UNIQUE_TITLE_INDEX} ON {DEFAULT_TABLE} (title)"
+    ))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Columns of [`DEFAULT_TABLE`] this version reads and writes, as the migrations create them
+pub const TODOS_COLUMNS: &[&str] = &[
+    "id",
+    "title",
+    "notes",
+    "completed",
+    "assigned",
+    "priority",
+    "created_at",
+    "updated_at",
+    "parent_id",
+    "position",
+    "completed_at",
+    "due_date",
+];
+
+/// Fail with the missing and unexpected columns when [`DEFAULT_TABLE`] doesn't match [`TODOS_COLUMNS`]
+///
+/// Meant to run after [`migrate`], so a database changed by hand or by another
+/// version is reported at startup instead of as a failing query later.
+pub async fn check_schema(pool: &SqlitePool) -> Result<()> {
+    let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?1)")
+        .bind(DEFAULT_TABLE)
+        .fetch_all(pool)
+        .await?;
+    if columns.is_empty() {
+        bail!("table {DEFAULT_TABLE} does not exist, run the migrations first");
+    }
+    let missing: Vec<&str> = TODOS_COLUMNS
+        .iter()
+        .copied()
+        .filter(|expected| !columns.iter().any(|column| column == expected))
+        .collect();
+    let extra: Vec<&str> = columns
+        .iter()
+        .map(String::as_str)
+        .filter(|column| !TODOS_COLUMNS.contains(column))
+        .collect();
+    let mut problems = Vec::new();
+    if !missing.is_empty() {
+        problems.push(format!("missing columns {}", missing.join(", ")));
+    }
+    if !extra.is_empty() {
+        problems.push(format!("unexpected columns {}", extra.join(", ")));
+    }
+    if !problems.is_empty() {
+        bail!(
+            "table {DEFAULT_TABLE} does not match this version: {}",
+            problems.join("; ")
+        );
+    }
+    Ok(())
+}
+
+/// Whether `url` points to an in-memory SQLite database
+pub fn is_memory_url(url: &str) -> bool {
+    let (database, params) = url.split_once('?').unwrap_or((url, ""));
+    let database = database
+        .trim_start_matches("sqlite:")
+        .trim_start_matches("//");
+    database == ":memory:"
+        || database == "file::memory:"
+        || params.split('&').any(|param| param == "mode=memory")
+}
+
+/// How long a connection waits for a lock held by another one before failing
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `PRAGMA synchronous` unless `SQLITE_SYNCHRONOUS` says otherwise
+pub const DEFAULT_SYNCHRONOUS: SqliteSynchronous = SqliteSynchronous::Normal;
+
+/// `PRAGMA journal_mode` unless `SQLITE_JOURNAL_MODE` says otherwise
+pub const DEFAULT_JOURNAL_MODE: SqliteJournalMode = SqliteJournalMode::Wal;
+
+/// Durability setting from `SQLITE_SYNCHRONOUS`, e.g. `normal` or `full`
+///
+/// With WAL, `NORMAL` never corrupts the database but a power loss may undo the
+/// last commits. `FULL` syncs on every commit and is slower.
+pub fn synchronous() -> SqliteSynchronous {
+    synchronous_from(std::env::var("SQLITE_SYNCHRONOUS").ok())
+}
+
+fn synchronous_from(var: Option<String>) -> SqliteSynchronous {
+    match var.as_deref().map(str::trim) {
+        None | Some("") => DEFAULT_SYNCHRONOUS,
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            tracing::warn!("SQLITE_SYNCHRONOUS={value} is not a synchronous mode, using NORMAL");
+            DEFAULT_SYNCHRONOUS
+        }),
+    }
+}
+
+/// Journal mode from `SQLITE_JOURNAL_MODE`, e.g. `wal` or `delete`
+///
+/// WAL lets readers run next to a writer but keeps `-wal` and `-shm` files next to
+/// the database, which network file systems don't support.
+pub fn journal_mode() -> SqliteJournalMode {
+    journal_mode_from(std::env::var("SQLITE_JOURNAL_MODE").ok())
+}
+
+fn journal_mode_from(var: Option<String>) -> SqliteJournalMode {
+    match var.as_deref().map(str::trim) {
+        None | Some("") => DEFAULT_JOURNAL_MODE,
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            tracing::warn!("SQLITE_JOURNAL_MODE={value} is not a journal mode, using WAL");
+            DEFAULT_JOURNAL_MODE
+        }),
+    }
+}
+
+/// Connect options for `url`
+///
+/// Every connection enforces foreign keys and waits up to [`BUSY_TIMEOUT`] on locks.
+/// It applies [`synchronous`] and, for databases on disk, [`journal_mode`].
+/// In-memory databases are only shared by connections with a shared cache. sqlx
+/// gives every parsed `sqlite::memory:` URL its own named database, so each pool
+/// still gets a private database while its connections see the same data.
+pub fn connect_options(url: &str) -> Result<SqliteConnectOptions> {
+    let options = SqliteConnectOptions::from_str(url)?
+        .foreign_keys(true)
+        .busy_timeout(BUSY_TIMEOUT)
+        .synchronous(synchronous());
+    if is_memory_url(url) {
+        return Ok(options.shared_cache(true));
+    }
+    Ok(options.journal_mode(journal_mode()))
+}
+
+/// Pool options for `url`
+///
+/// An in-memory database is dropped together with its last connection, so such
+/// pools keep one connection open for their whole lifetime.
+pub fn pool_options(url: &str) -> SqlitePoolOptions {
+    let options = SqlitePoolOptions::new();
+    if is_memory_url(url) {
+        return options
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None);
+    }
+    options
+}
+
+/// Connect a pool to `url`, in-memory databases survive across connections
+pub async fn connect(url: &str) -> Result<SqlitePool> {
+    Ok(pool_options(url)
+        .connect_with(connect_options(url)?)
+        .await?)
+}
+
+/// Connection attempts unless `DB_CONNECT_RETRIES` says otherwise
+pub const DEFAULT_CONNECT_RETRIES: u32 = 5;
+
+/// Delay before the second attempt, doubled after every failure
+const CONNECT_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Number of connection attempts from `DB_CONNECT_RETRIES`, at least one
+pub fn connect_retries() -> u32 {
+    connect_retries_from(std::env::var("DB_CONNECT_RETRIES").ok())
+}
+
+fn connect_retries_from(var: Option<String>) -> u32 {
+    match var.as_deref().map(str::trim) {
+        None | Some("") => DEFAULT_CONNECT_RETRIES,
+        Some(value) => value
+            .parse()
+            .map(|retries: u32| retries.max(1))
+            .unwrap_or_else(|_| {
+                tracing::warn!(
+                    "DB_CONNECT_RETRIES={value} is not a number, using {DEFAULT_CONNECT_RETRIES}"
+                );
+                DEFAULT_CONNECT_RETRIES
+            }),
+    }
+}
+
+/// Like [`connect`], retrying [`connect_retries`] times for databases that start slowly
+pub async fn connect_with_retry(url: &str) -> Result<SqlitePool> {
+    retry(connect_retries(), CONNECT_BACKOFF, || connect(url)).await
+}
+
+/// Run `attempt` until it succeeds or `attempts` runs failed, backing off in between
+async fn retry<T, F, Fut>(attempts: u32, backoff: Duration, mut attempt: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut delay = backoff;
+    let mut tried = 1;
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(err) if tried < attempts => {
+                tracing::warn!(
+                    "connection attempt {tried}/{attempts} failed, retrying in {delay:?}: {err:#}"
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                tried += 1;
+            }
+            Err(err) => {
+                return Err(err.context(format!("giving up after {tried} connection attempt(s)")))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_database_url() {
+        let url = database_url_from(Ok("sqlite:other.db".to_owned())).unwrap();
+        assert_eq!(url, "sqlite:other.db");
+        let url = database_url_from(Err(VarError::NotPresent)).unwrap();
+        assert_eq!(url, DEFAULT_DATABASE_URL);
+        let err = database_url_from(Ok(" ".to_owned())).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("example: DATABASE_URL=sqlite://todos.db"));
+    }
+
+    #[test]
+    fn test_is_memory_url() {
+        assert!(is_memory_url("sqlite::memory:"));
+        assert!(is_memory_url("sqlite://:memory:"));
+        assert!(is_memory_url("sqlite:file::memory:?cache=shared"));
+        assert!(is_memory_url("sqlite://todos?mode=memory&cache=shared"));
+        assert!(!is_memory_url("sqlite:todos.db"));
+        assert!(!is_memory_url("sqlite://todos.db?mode=rwc"));
+    }
+
+    #[tokio::test]
+    async fn test_memory_shared_across_connections() {
+        let pool = pool_options("sqlite::memory:")
+            .max_connections(4)
+            .connect_with(connect_options("sqlite::memory:?cache=private").unwrap())
+            .await
+            .unwrap();
+        let mut first = pool.acquire().await.unwrap();
+        let mut second = pool.acquire().await.unwrap();
+        sqlx::query("CREATE TABLE items (name TEXT NOT NULL)")
+            .execute(&mut *first)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO items (name) VALUES ('shared')")
+            .execute(&mut *first)
+            .await
+            .unwrap();
+        let name: String = sqlx::query_scalar("SELECT name FROM items")
+            .fetch_one(&mut *second)
+            .await
+            .unwrap();
+        assert_eq!(name, "shared");
+        assert_eq!(pool.size(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_memory_pools_are_isolated() {
+        let first = connect("sqlite::memory:").await.unwrap();
+        let second = connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE items (name TEXT NOT NULL)")
+            .execute(&first)
+            .await
+            .unwrap();
+        assert!(sqlx::query("SELECT name FROM items")
+            .fetch_all(&second)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_pragmas_from() {
+        assert!(matches!(synchronous_from(None), SqliteSynchronous::Normal));
+        assert!(matches!(
+            synchronous_from(Some("FULL".to_owned())),
+            SqliteSynchronous::Full
+        ));
+        assert!(matches!(
+            synchronous_from(Some("safe".to_owned())),
+            SqliteSynchronous::Normal
+        ));
+        assert!(matches!(journal_mode_from(None), SqliteJournalMode::Wal));
+        assert!(matches!(
+            journal_mode_from(Some("delete".to_owned())),
+            SqliteJournalMode::Delete
+        ));
+        assert!(matches!(
+            journal_mode_from(Some("fast".to_owned())),
+            SqliteJournalMode::Wal
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pragmas_set() {
+        let path = std::env::temp_dir().join(format!("todo-pragmas-{}.db", std::process::id()));
+        let url = format!("sqlite://{}?mode=rwc", path.display());
+        let pool = connect(&url).await.unwrap();
+        // 1 is NORMAL
+        let synchronous: i64 = sqlx::query_scalar("PRAGMA synchronous")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(synchronous, 1);
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        pool.close().await;
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+
+    #[test]
+    fn test_unique_titles_from() {
+        assert!(!unique_titles_from(None));
+        assert!(!unique_titles_from(Some("0".to_owned())));
+        assert!(unique_titles_from(Some("true".to_owned())));
+        assert!(unique_titles_from(Some(" 1 ".to_owned())));
+        assert!(!unique_titles_from(Some("sure".to_owned())));
+    }
+
+    #[tokio::test]
+    async fn test_set_unique_titles() {
+        let pool = connect("sqlite::memory:").await.unwrap();
+        migrate(&pool).await.unwrap();
+        for title in ["Buy milk", "Buy milk", "Walk the dog"] {
+            sqlx::query("INSERT INTO todos (title) VALUES (?1)")
+                .bind(title)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let err = set_unique_titles(&pool, true).await.unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("rename or delete them first: Buy milk"));
+        sqlx::query("DELETE FROM todos WHERE id = 2")
+            .execute(&pool)
+            .await
+            .unwrap();
+        set_unique_titles(&pool, true).await.unwrap();
+        assert!(sqlx::query("INSERT INTO todos (title) VALUES ('Buy milk')")
+            .execute(&pool)
+            .await
+            .is_err());
+        set_unique_titles(&pool, false).await.unwrap();
+        sqlx::query("INSERT INTO todos (title) VALUES ('Buy milk')")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_schema() {
+        let pool = connect("sqlite::memory:").await.unwrap();
+        let err = check_schema(&pool).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "table todos does not exist, run the migrations first"
+        );
+        migrate(&pool).await.unwrap();
+        check_schema(&pool).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_schema_mismatch() {
+        let pool = connect("sqlite::memory:").await.unwrap();
+        sqlx::query(
+            "CREATE TABLE todos (id INTEGER PRIMARY KEY NOT NULL, title TEXT NOT NULL, description TEXT, completed BOOLEAN, assigned TEXT, priority INTEGER, created_at DATETIME, updated_at DATETIME, parent_id INTEGER, position INTEGER)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let err = check_schema(&pool).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "table todos does not match this version: missing columns notes, completed_at, due_date; unexpected columns description"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_foreign_keys_enforced() {
+        let pool = connect("sqlite::memory:").await.unwrap();
+        let enabled: bool = sqlx::query_scalar("PRAGMA foreign_keys")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(enabled);
+        sqlx::query("CREATE TABLE parents (id INTEGER PRIMARY KEY NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE children (id INTEGER PRIMARY KEY NOT NULL, parent_id INTEGER REFERENCES parents(id))",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert!(sqlx::query("INSERT INTO children (parent_id) VALUES (42)")
+            .execute(&pool)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retry_succeeds_on_second_attempt() {
+        let mut calls = 0;
+        let value = retry(3, Duration::from_millis(1), || {
+            calls += 1;
+            let call = calls;
+            async move {
+                if call < 2 {
+                    bail!("database not ready");
+                }
+                Ok(call)
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(value, 2);
+        assert_eq!(calls, 2);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let mut calls = 0;
+        let err = retry(3, Duration::from_millis(1), || {
+            calls += 1;
+            async { Result::<()>::Err(anyhow::anyhow!("database not ready")) }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(calls, 3);
+        assert!(format!("{err:#}").contains("giving up after 3 connection attempt(s)"));
+    }
+
+    #[test]
+    fn test_connect_retries_from() {
+        assert_eq!(connect_retries_from(None), DEFAULT_CONNECT_RETRIES);
+        assert_eq!(
+            connect_retries_from(Some("abc".to_owned())),
+            DEFAULT_CONNECT_RETRIES
+        );
+        assert_eq!(connect_retries_from(Some("0".to_owned())), 1);
+        assert_eq!(connect_retries_from(Some("10".to_owned())), 10);
+    }
+}
@@ -1,4 +1,10 @@
 pub mod todo;
 pub mod cli;
 pub mod pagination;
-pub mod command;
\ No newline at end of file
+pub mod sort;
+pub mod query;
+pub mod command;
+pub mod api;
+pub mod db;
+#[path = "todo-weg.rs"]
+pub mod todo_weg;
@@ -1,10 +1,66 @@
+use std::sync::OnceLock;
+
 use serde::{Deserialize, Serialize};
 
+/// Page size used when neither the request nor `DEFAULT_PAGE_LIMIT` sets one
+pub const FALLBACK_PAGE_LIMIT: u32 = 50;
+
+/// Page size applied when `limit` is missing, from `DEFAULT_PAGE_LIMIT`
+///
+/// Shared by the API and the CLI, read once per process.
+pub fn default_limit() -> u32 {
+    static LIMIT: OnceLock<u32> = OnceLock::new();
+    *LIMIT.get_or_init(|| default_limit_from(std::env::var("DEFAULT_PAGE_LIMIT").ok()))
+}
+
+fn default_limit_from(var: Option<String>) -> u32 {
+    match var.as_deref().map(str::trim) {
+        None | Some("") => FALLBACK_PAGE_LIMIT,
+        Some(value) => match value.parse() {
+            Ok(limit) if limit > 0 => limit,
+            _ => {
+                tracing::warn!(
+                    "DEFAULT_PAGE_LIMIT={value} is not a positive number, using {FALLBACK_PAGE_LIMIT}"
+                );
+                FALLBACK_PAGE_LIMIT
+            }
+        },
+    }
+}
 
+/// Largest offset accepted by [`Pagination::validate`]
+pub const MAX_OFFSET: u32 = 1_000_000;
+
+/// Why a [`Pagination`] was rejected
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum PaginationError {
+    #[error("limit must be greater than 0")]
+    ZeroLimit,
+    #[error("limit must be at most {max}, got {limit}")]
+    LimitTooLarge { limit: u32, max: u32 },
+    #[error("offset must be at most {max}, got {offset}")]
+    OffsetTooLarge { offset: u32, max: u32 },
+}
+
+/// One page of results together with the size of the whole collection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Number of items across all pages
+    pub total: i64,
+    /// 1-based page number
+    pub page: u32,
+    pub per_page: u32,
+}
 
 /// Parameters for pagination
 ///
 /// Used to demonstrate handling of query parameters.
+///
+/// - `limit: None` returns a page of [`default_limit`] rows, `limit: Some(0)` returns no rows.
+///   The API rejects `limit=0` with a 400 since it is almost always a client bug.
+/// - `offset: None` starts at the first row, an offset past the end returns an
+///   empty list rather than an error.
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Pagination {
     pub offset: Option<u32>,
@@ -15,4 +71,118 @@ impl Pagination {
     pub fn new(offset: Option<u32>, limit: Option<u32>) -> Pagination {
         Pagination { offset, limit }
     }
+
+    /// Every row, for internal callers that must not stop at the default page size
+    pub fn all() -> Pagination {
+        Pagination::new(None, Some(u32::MAX))
+    }
+
+    /// Rows to skip
+    pub fn offset(&self) -> u32 {
+        self.offset.unwrap_or(0)
+    }
+
+    /// Maximum rows to return, [`default_limit`] when no limit is set
+    pub fn limit(&self) -> u32 {
+        self.limit.unwrap_or_else(default_limit)
+    }
+
+    /// Whether `limit=0` was requested explicitly
+    pub fn is_zero_limit(&self) -> bool {
+        self.limit == Some(0)
+    }
+
+    /// Reject `limit=0`, limits above `max_limit` and offsets above [`MAX_OFFSET`]
+    ///
+    /// A missing limit stays valid, [`Pagination::new`] never checks anything.
+    pub fn validate(&self, max_limit: u32) -> Result<(), PaginationError> {
+        match (self.limit, self.offset) {
+            (Some(0), _) => Err(PaginationError::ZeroLimit),
+            (Some(limit), _) if limit > max_limit => Err(PaginationError::LimitTooLarge {
+                limit,
+                max: max_limit,
+            }),
+            (_, Some(offset)) if offset > MAX_OFFSET => Err(PaginationError::OffsetTooLarge {
+                offset,
+                max: MAX_OFFSET,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let pagination = Pagination::default();
+        assert_eq!(pagination.offset(), 0);
+        assert_eq!(pagination.limit(), default_limit());
+        assert!(!pagination.is_zero_limit());
+        assert_eq!(Pagination::all().limit(), u32::MAX);
+    }
+
+    #[test]
+    fn test_zero_limit() {
+        let pagination = Pagination::new(None, Some(0));
+        assert_eq!(pagination.limit(), 0);
+        assert!(pagination.is_zero_limit());
+    }
+
+    #[test]
+    fn test_explicit_values() {
+        let pagination = Pagination::new(Some(5), Some(10));
+        assert_eq!(pagination.offset(), 5);
+        assert_eq!(pagination.limit(), 10);
+        assert!(!pagination.is_zero_limit());
+    }
+
+    #[test]
+    fn test_validate_in_range() {
+        assert_eq!(Pagination::default().validate(100), Ok(()));
+        assert_eq!(
+            Pagination::new(Some(MAX_OFFSET), Some(100)).validate(100),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_validate_out_of_range() {
+        assert_eq!(
+            Pagination::new(None, Some(0)).validate(100),
+            Err(PaginationError::ZeroLimit)
+        );
+        assert_eq!(
+            Pagination::new(None, Some(101)).validate(100),
+            Err(PaginationError::LimitTooLarge {
+                limit: 101,
+                max: 100
+            })
+        );
+        assert_eq!(
+            Pagination::new(Some(MAX_OFFSET + 1), None).validate(100),
+            Err(PaginationError::OffsetTooLarge {
+                offset: MAX_OFFSET + 1,
+                max: MAX_OFFSET
+            })
+        );
+    }
+
+    #[test]
+    fn test_default_limit_from() {
+        assert_eq!(default_limit_from(None), FALLBACK_PAGE_LIMIT);
+        assert_eq!(default_limit_from(Some("".to_owned())), FALLBACK_PAGE_LIMIT);
+        assert_eq!(
+            default_limit_from(Some("0".to_owned())),
+            FALLBACK_PAGE_LIMIT
+        );
+        assert_eq!(
+            default_limit_from(Some("many".to_owned())),
+            FALLBACK_PAGE_LIMIT
+        );
+        assert_eq!(default_limit_from(Some("20".to_owned())), 20);
+    }
 }
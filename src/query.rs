@@ -0,0 +1,30 @@
+/// Character escaping wildcards in [`escape_like`] output
+pub const LIKE_ESCAPE: char = '\\';
+
+/// Escape `%`, `_` and `\` so `term` matches literally in `LIKE ... ESCAPE '\'`
+pub fn escape_like(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | LIKE_ESCAPE) {
+            escaped.push(LIKE_ESCAPE);
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("plain"), "plain");
+        assert_eq!(escape_like("%"), "\\%");
+        assert_eq!(escape_like("_"), "\\_");
+        assert_eq!(escape_like("\\"), "\\\\");
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+        assert_eq!(escape_like("ünï_cødé"), "ünï\\_cødé");
+    }
+}
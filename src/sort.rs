@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// Ordering of todo lists
+#[derive(Serialize, Deserialize, clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    /// Most urgent first, ties in creation order
+    #[default]
+    Priority,
+    /// Creation order
+    Id,
+    /// Alphabetical by title, ignoring case
+    Title,
+}
+
+impl SortBy {
+    /// SQL `ORDER BY` expression for this ordering
+    pub fn order_by(&self) -> &'static str {
+        match self {
+            SortBy::Priority => "priority DESC, id ASC",
+            SortBy::Id => "id ASC",
+            SortBy::Title => "title COLLATE NOCASE ASC, id ASC",
+        }
+    }
+
+    /// Like [`SortBy::order_by`] with every column flipped, ties included
+    pub fn order_by_reversed(&self) -> &'static str {
+        match self {
+            SortBy::Priority => "priority ASC, id DESC",
+            SortBy::Id => "id DESC",
+            SortBy::Title => "title COLLATE NOCASE DESC, id DESC",
+        }
+    }
+}
+
+/// Query parameter selecting the list order
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Sort {
+    pub sort: Option<SortBy>,
+}
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{ AtomicUsize,Ordering};
-use sqlx::sqlite::SqlitePool;
-use anyhow::Result;
 
 use serde::{Deserialize, Serialize};
 
@@ -35,7 +36,7 @@ impl IdentifiableTodoItem {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Pagination {
     pub offset: Option<usize>,
     pub limit: Option<usize>,
@@ -47,15 +48,6 @@ impl Pagination {
     }
 }
 
-impl Default for Pagination {
-    fn default() -> Self {
-        Pagination{
-            offset: None,
-            limit: None
-        }
-    }
-}
-
 #[derive(thiserror::Error, Debug)]
 pub enum TodoStoreError {
     #[error("persistent data store Error")]
@@ -65,40 +57,11 @@ pub enum TodoStoreError {
 }
 
 
-#[allow(dead_code)]
-#[derive(Clone, Debug)]
-pub struct TodoRepository {
-    pool: SqlitePool,
-}
-
-impl TodoRepository {
-    pub fn new(pool: SqlitePool ) -> TodoRepository {
-        TodoRepository{pool}
-    }
-
-    fn get_todo(&self, id: usize) -> Result<Option<IdentifiableTodoItem>> {
-        let find_query = r#"
-        select todos.* 
-        from todos 
-        where todos.id=$1"#;
-
-        let items = sqlx::query_as::<_, IdentifiableTodoItem>(find_query)
-            .bind(id)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(|err| match err {
-                sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-                _ => RepositoryError::Unexpected(err.to_string()),
-            })?;
-        None
-    }
-}
-
-
-
 pub struct TodoStore {
     store: HashMap<usize, IdentifiableTodoItem>,
     id_generator: AtomicUsize,
+	/// File every change is saved to, see [`TodoStore::with_file`]
+	path: Option<PathBuf>,
 }
 
 impl TodoStore {
@@ -107,8 +70,46 @@ impl TodoStore {
         TodoStore {
             store,
             id_generator,
+            path: None,
         }
     }
+	/// Store backed by `path` for demos, loaded from it when it exists
+	///
+	/// Every add, update and remove rewrites the file. A failed write is logged
+	/// and the change is kept in memory.
+	pub fn with_file(path: impl Into<PathBuf>) -> Result<TodoStore, TodoStoreError> {
+		let path = path.into();
+		let mut store = if path.exists() {
+			TodoStore::load_from_file(&path)?
+		} else {
+			TodoStore::default()
+		};
+		store.path = Some(path);
+		Ok(store)
+	}
+	/// Load a store saved by [`TodoStore::save_to_file`]
+	pub fn load_from_file(path: impl AsRef<Path>) -> Result<TodoStore, TodoStoreError> {
+		let reader = BufReader::new(File::open(path)?);
+		let todos: Vec<IdentifiableTodoItem> = serde_json::from_reader(reader)?;
+		Ok(TodoStore::from_hashmap(todos.into_iter().map(|todo| (todo.id, todo)).collect()))
+	}
+	/// Write every todo to `path` as a JSON array ordered by id, replacing the file
+	pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<(), TodoStoreError> {
+		let mut todos: Vec<&IdentifiableTodoItem> = self.store.values().collect();
+		todos.sort_by_key(|todo| todo.id);
+		let mut writer = BufWriter::new(File::create(path)?);
+		serde_json::to_writer_pretty(&mut writer, &todos)?;
+		writer.flush()?;
+		Ok(())
+	}
+	/// Save to the backing file of [`TodoStore::with_file`], if any
+	fn persist(&self) {
+		if let Some(path) = &self.path {
+			if let Err(err) = self.save_to_file(path) {
+				tracing::warn!("failed to save todos to {}: {err:?}", path.display());
+			}
+		}
+	}
 	/// Get list of todo
 	/// 
 	/// Support pagination
@@ -124,36 +125,52 @@ impl TodoStore {
 	pub fn get_todo(&self,id: usize) -> Option<&IdentifiableTodoItem> {
 		self.store.get(&id)	
 	}
+	/// Next id that is not taken by a stored todo
+	///
+	/// The generator only moves forward, so a removed id is never handed out again,
+	/// and ids already present in the store are skipped.
+	fn next_id(&mut self) -> usize {
+		loop {
+			let id = self.id_generator.fetch_add(1, Ordering::Relaxed);
+			if !self.store.contains_key(&id) {
+				return id;
+			}
+		}
+	}
 	/// Create new todo
 	pub fn add_todo(&mut self, todo: TodoItem) -> IdentifiableTodoItem {
-		let id = self.id_generator.fetch_add(1, Ordering::Relaxed);
+		let id = self.next_id();
 		let new_todo = IdentifiableTodoItem{id,todo};
 		self.store.insert(id,new_todo.clone());
+		self.persist();
 		new_todo
 	}
 	/// Remove todo item by id
 	pub fn remove_todo(&mut self, id: usize) -> Option<IdentifiableTodoItem>{
-		self.store.remove(&id)
+		let removed = self.store.remove(&id);
+		if removed.is_some() {
+			self.persist();
+		}
+		removed
 	}
 
 	/// Update todo item by id
 	pub fn update_item(&mut self, id: usize,todo: UpdateTodoItem) -> Option<&IdentifiableTodoItem> {
-		if let Some(item) = self.store.get_mut(&id) {
-			if let Some(title) = todo.title {
-				item.todo.title = title;
-			}
-			if let Some(notes) = todo.notes {
-				item.todo.notes = notes;
-			}
-			if let Some(assigned) = todo.assigned {
-				item.todo.assigned = assigned;
-			}
-			if let Some(completed) = todo.completed {
-				item.todo.completed = completed;
-			}
-			return Some(item);
+		let item = self.store.get_mut(&id)?;
+		if let Some(title) = todo.title {
+			item.todo.title = title;
+		}
+		if let Some(notes) = todo.notes {
+			item.todo.notes = notes;
+		}
+		if let Some(assigned) = todo.assigned {
+			item.todo.assigned = assigned;
 		}
-		None
+		if let Some(completed) = todo.completed {
+			item.todo.completed = completed;
+		}
+		self.persist();
+		self.store.get(&id)
 	}
 }
 
@@ -162,4 +179,93 @@ impl Default for TodoStore {
 	fn default() -> Self {
 		TodoStore::from_hashmap(HashMap::new())
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn item(title: &str) -> TodoItem {
+        TodoItem {
+            title: title.to_owned(),
+            notes: String::new(),
+            assigned: String::new(),
+            completed: false,
+        }
+    }
+
+    #[test]
+    fn test_add_after_loading_map_with_gap() {
+        let store = [0, 1, 5]
+            .into_iter()
+            .map(|id| (id, IdentifiableTodoItem::new(id, item("loaded"))))
+            .collect();
+        let mut store = TodoStore::from_hashmap(store);
+        let added = store.add_todo(item("new"));
+        assert_eq!(added.id, 6);
+        assert_eq!(store.get_todo(5).unwrap().todo.title, "loaded");
+        // removing the newest todo does not free its id
+        store.remove_todo(6);
+        assert_eq!(store.add_todo(item("newer")).id, 7);
+        assert_eq!(store.get_todos(Pagination::default()).len(), 4);
+    }
+
+    #[test]
+    fn test_save_and_load_file() {
+        let path = std::env::temp_dir().join(format!("todo-store-{}.json", std::process::id()));
+        let mut store = TodoStore::default();
+        store.add_todo(item("first"));
+        let second = store.add_todo(TodoItem {
+            notes: "with notes".to_owned(),
+            completed: true,
+            ..item("second")
+        });
+        store.save_to_file(&path).unwrap();
+
+        let mut loaded = TodoStore::load_from_file(&path).unwrap();
+        assert_eq!(loaded.get_todos(Pagination::default()).len(), 2);
+        let todo = &loaded.get_todo(second.id).unwrap().todo;
+        assert_eq!(todo.title, "second");
+        assert_eq!(todo.notes, "with notes");
+        assert!(todo.completed);
+        // ids continue after the loaded ones
+        assert_eq!(loaded.add_todo(item("third")).id, second.id + 1);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            TodoStore::load_from_file(&path),
+            Err(TodoStoreError::FileAccessError(_))
+        ));
+        std::fs::write(&path, "not json").unwrap();
+        assert!(matches!(
+            TodoStore::load_from_file(&path),
+            Err(TodoStoreError::SerializationError(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_with_file_saves_changes() {
+        let path = std::env::temp_dir().join(format!("todo-store-auto-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut store = TodoStore::with_file(&path).unwrap();
+        let first = store.add_todo(item("first"));
+        let second = store.add_todo(item("second"));
+        let update = UpdateTodoItem {
+            title: None,
+            notes: Some("edited".to_owned()),
+            assigned: None,
+            completed: Some(true),
+        };
+        store.update_item(first.id, update).unwrap();
+        store.remove_todo(second.id).unwrap();
+
+        let reloaded = TodoStore::with_file(&path).unwrap();
+        assert_eq!(reloaded.get_todos(Pagination::default()).len(), 1);
+        let todo = &reloaded.get_todo(first.id).unwrap().todo;
+        assert_eq!(todo.notes, "edited");
+        assert!(todo.completed);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,273 +1,4374 @@
-use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use sqlx::sqlite::SqlitePool;
+use sqlx::sqlite::{SqliteConnection, SqlitePool};
+use tokio::sync::broadcast;
+
+use super::pagination::{Page, Pagination};
+use super::query::escape_like;
+use super::sort::SortBy;
+
+/// Todo priority, stored as integer so it sorts naturally
+#[derive(
+    sqlx::Type,
+    Debug,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+pub enum Priority {
+    Low = 0,
+    #[default]
+    Normal = 1,
+    High = 2,
+    Urgent = 3,
+}
 
-use super::pagination::Pagination;
+impl Priority {
+    /// Every priority, least urgent first
+    pub const ALL: [Priority; 4] = [
+        Priority::Low,
+        Priority::Normal,
+        Priority::High,
+        Priority::Urgent,
+    ];
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+            Priority::Urgent => "urgent",
+        };
+        f.write_str(name)
+    }
+}
 
-#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
 pub struct Todo {
+    #[serde(deserialize_with = "id_serde::deserialize")]
+    #[cfg_attr(feature = "string-ids", serde(serialize_with = "id_serde::serialize"))]
+    #[cfg_attr(feature = "string-ids", schemars(with = "String"))]
     pub id: i64,
     pub title: String,
     pub notes: String,
     pub completed: bool,
+    pub assigned: String,
+    pub priority: Priority,
+    /// Todo this one is a subtask of
+    pub parent_id: Option<i64>,
+    /// Order among siblings, lower first
+    pub position: i64,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    /// When the todo was completed, `None` while it is open
+    pub completed_at: Option<NaiveDateTime>,
+    /// Day the todo should be done by
+    pub due_date: Option<NaiveDate>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+/// Column of [`Todo`] a client may select, see [`TodoRepository::list_fields`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoField {
+    Id,
+    Title,
+    Notes,
+    Completed,
+    Assigned,
+    Priority,
+    ParentId,
+    Position,
+    CreatedAt,
+    UpdatedAt,
+    CompletedAt,
+    DueDate,
+}
+
+impl TodoField {
+    /// Every field in the order [`Todo`] serializes them
+    pub const ALL: [TodoField; 12] = [
+        TodoField::Id,
+        TodoField::Title,
+        TodoField::Notes,
+        TodoField::Completed,
+        TodoField::Assigned,
+        TodoField::Priority,
+        TodoField::ParentId,
+        TodoField::Position,
+        TodoField::CreatedAt,
+        TodoField::UpdatedAt,
+        TodoField::CompletedAt,
+        TodoField::DueDate,
+    ];
+
+    /// Column name, also the JSON key
+    pub fn column(&self) -> &'static str {
+        match self {
+            TodoField::Id => "id",
+            TodoField::Title => "title",
+            TodoField::Notes => "notes",
+            TodoField::Completed => "completed",
+            TodoField::Assigned => "assigned",
+            TodoField::Priority => "priority",
+            TodoField::ParentId => "parent_id",
+            TodoField::Position => "position",
+            TodoField::CreatedAt => "created_at",
+            TodoField::UpdatedAt => "updated_at",
+            TodoField::CompletedAt => "completed_at",
+            TodoField::DueDate => "due_date",
+        }
+    }
+
+    /// Parse a comma separated list like `id,title`, keeping the first of repeated fields
+    pub fn parse_list(value: &str) -> Result<Vec<TodoField>, String> {
+        let mut fields = Vec::new();
+        for name in value.split(',').map(str::trim) {
+            let field = TodoField::ALL
+                .into_iter()
+                .find(|field| field.column() == name)
+                .ok_or_else(|| {
+                    let known: Vec<&str> =
+                        TodoField::ALL.iter().map(|field| field.column()).collect();
+                    format!(
+                        "unknown field `{name}`, expected one of {}",
+                        known.join(", ")
+                    )
+                })?;
+            if !fields.contains(&field) {
+                fields.push(field);
+            }
+        }
+        Ok(fields)
+    }
+
+    /// Read this field from `row` as it would appear in a serialized [`Todo`]
+    fn value(&self, row: &sqlx::sqlite::SqliteRow) -> Result<serde_json::Value> {
+        use sqlx::Row;
+        let column = self.column();
+        let value = match self {
+            #[cfg(feature = "string-ids")]
+            TodoField::Id => serde_json::Value::String(row.try_get::<i64, _>(column)?.to_string()),
+            #[cfg(not(feature = "string-ids"))]
+            TodoField::Id => row.try_get::<i64, _>(column)?.into(),
+            TodoField::Title | TodoField::Notes | TodoField::Assigned => {
+                row.try_get::<String, _>(column)?.into()
+            }
+            TodoField::Completed => row.try_get::<bool, _>(column)?.into(),
+            TodoField::Priority => to_json(row.try_get::<Priority, _>(column)?),
+            TodoField::ParentId => row.try_get::<Option<i64>, _>(column)?.into(),
+            TodoField::Position => row.try_get::<i64, _>(column)?.into(),
+            TodoField::CreatedAt | TodoField::UpdatedAt => {
+                to_json(row.try_get::<NaiveDateTime, _>(column)?)
+            }
+            TodoField::CompletedAt => to_json(row.try_get::<Option<NaiveDateTime>, _>(column)?),
+            TodoField::DueDate => to_json(row.try_get::<Option<NaiveDate>, _>(column)?),
+        };
+        Ok(value)
+    }
+}
+
+/// JSON of a plain value, those serializers can't fail
+fn to_json(value: impl Serialize) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or(serde_json::Value::Null)
+}
+
+/// Ids as JSON strings, see the `string-ids` feature
+///
+/// Numbers and numeric strings are both accepted on input.
+mod id_serde {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[cfg_attr(not(feature = "string-ids"), allow(dead_code))]
+    pub fn serialize<S: Serializer>(id: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(id)
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Number(i64),
+        String(String),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+        match Id::deserialize(deserializer)? {
+            Id::Number(id) => Ok(id),
+            Id::String(id) => id
+                .parse()
+                .map_err(|_| serde::de::Error::custom(format!("invalid todo id `{id}`"))),
+        }
+    }
+}
+
+/// Completion flags sent as `true`, `1` or `"true"` by different clients
+///
+/// Anything but a bool, `0`/`1` or `"true"`/`"false"` is rejected.
+mod lenient_bool {
+    use serde::{Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Bool(bool),
+        Number(i64),
+        String(String),
+    }
+
+    impl Flag {
+        fn parse<E: serde::de::Error>(self) -> Result<bool, E> {
+            match self {
+                Flag::Bool(value) => Ok(value),
+                Flag::Number(0) => Ok(false),
+                Flag::Number(1) => Ok(true),
+                Flag::String(value) if value == "true" => Ok(true),
+                Flag::String(value) if value == "false" => Ok(false),
+                Flag::Number(value) => Err(E::custom(format!(
+                    "invalid boolean `{value}`, expected true, false, 0 or 1"
+                ))),
+                Flag::String(value) => Err(E::custom(format!(
+                    "invalid boolean `{value}`, expected true, false, 0 or 1"
+                ))),
+            }
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        Flag::deserialize(deserializer)?.parse()
+    }
+
+    pub fn deserialize_option<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<bool>, D::Error> {
+        Option::<Flag>::deserialize(deserializer)?
+            .map(Flag::parse)
+            .transpose()
+    }
+}
+
+impl Todo {
+    /// Open todo `id` titled `Test todo {id}`, created and last updated at `created_at`
+    ///
+    /// Tests change the fields they care about with struct update syntax.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn fixture(id: i64, created_at: NaiveDateTime) -> Todo {
+        Todo {
+            id,
+            title: format!("Test todo {id}"),
+            notes: String::new(),
+            completed: false,
+            assigned: String::new(),
+            priority: Priority::Normal,
+            parent_id: None,
+            position: 0,
+            created_at,
+            updated_at: created_at,
+            completed_at: None,
+            due_date: None,
+        }
+    }
+    /// Single line representation like `[x] #3 Buy milk`
+    pub fn to_line(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Todo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = if self.completed { "x" } else { " " };
+        write!(f, "[{mark}] #{} {}", self.id, self.title)
+    }
+}
+
+/// Todo counts, see [`TodoRepository::stats`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TodoStats {
+    pub total: i64,
+    pub open: i64,
+    pub completed: i64,
+    /// Open todos due before today, see [`TodoRepository::count_overdue`]
+    pub overdue: i64,
+    /// Every priority is present, with `0` when no todo has it
+    pub by_priority: BTreeMap<Priority, i64>,
+}
+
+impl fmt::Display for TodoStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "total:     {}", self.total)?;
+        writeln!(f, "open:      {}", self.open)?;
+        writeln!(f, "completed: {}", self.completed)?;
+        writeln!(f, "overdue:   {}", self.overdue)?;
+        let by_priority = self
+            .by_priority
+            .iter()
+            .rev()
+            .map(|(priority, count)| format!("{priority} {count}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "priority:  {by_priority}")
+    }
+}
+
+/// New todo, only `title` is required
+///
+/// Omitted fields default to empty `notes`, `completed: false`, normal priority and no due date.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Default)]
 pub struct CreateTodo {
-    title: String,
+    pub title: String,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default, deserialize_with = "lenient_bool::deserialize")]
+    pub completed: bool,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub due_date: Option<NaiveDate>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default)]
 pub struct UpdateTodo {
-    title: Option<String>,
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    #[serde(default, deserialize_with = "lenient_bool::deserialize_option")]
+    pub completed: Option<bool>,
+    pub priority: Option<Priority>,
+}
+
+/// Why a repository operation failed
+#[derive(thiserror::Error, Debug)]
+pub enum RepositoryError {
+    #[error("todo {0} not found")]
+    NotFound(i64),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error(transparent)]
+    Database(sqlx::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<sqlx::Error> for RepositoryError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                RepositoryError::Conflict(db.message().to_owned())
+            }
+            err => RepositoryError::Database(err),
+        }
+    }
+}
+
+/// Result of every [`TodoRepository`] operation
+pub type Result<T, E = RepositoryError> = std::result::Result<T, E>;
+
+/// Longest accepted title, in characters
+pub const MAX_TITLE_LEN: usize = 200;
+
+/// Longest accepted notes, in characters
+pub const MAX_NOTES_LEN: usize = 10_000;
+
+/// Every rule a todo payload breaks, not just the first one
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("{}", .violations.join("; "))]
+pub struct ValidationError {
+    pub violations: Vec<String>,
+}
+
+impl ValidationError {
+    fn check(violations: Vec<String>) -> Result<(), ValidationError> {
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { violations })
+        }
+    }
+}
+
+/// Title as stored: surrounding whitespace trimmed, inner runs of whitespace turned into one space
+///
+/// This is lossy, tabs, newlines and repeated spaces in a title don't survive it.
+pub fn normalize_title(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn check_title(title: &str, violations: &mut Vec<String>) {
+    if title.trim().is_empty() {
+        violations.push("title must not be empty".to_owned());
+    }
+    if title.chars().count() > MAX_TITLE_LEN {
+        violations.push(format!("title must be at most {MAX_TITLE_LEN} characters"));
+    }
+}
+
+fn check_notes(notes: &str, violations: &mut Vec<String>) {
+    if notes.chars().count() > MAX_NOTES_LEN {
+        violations.push(format!("notes must be at most {MAX_NOTES_LEN} characters"));
+    }
+}
+
+impl CreateTodo {
+    /// Check the payload before it reaches the database
+    ///
+    /// `priority` needs no check, deserialization only accepts known levels.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut violations = Vec::new();
+        check_title(&self.title, &mut violations);
+        check_notes(&self.notes, &mut violations);
+        ValidationError::check(violations)
+    }
+}
+
+impl UpdateTodo {
+    /// Check the fields that are present, see [`CreateTodo::validate`]
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut violations = Vec::new();
+        if let Some(title) = &self.title {
+            check_title(title, &mut violations);
+        }
+        if let Some(notes) = &self.notes {
+            check_notes(notes, &mut violations);
+        }
+        ValidationError::check(violations)
+    }
+}
+
+impl CreateTodo {
+    /// Start building a todo, see [`CreateTodoBuilder`]
+    pub fn builder() -> CreateTodoBuilder {
+        CreateTodoBuilder::default()
+    }
+}
+
+impl UpdateTodo {
+    /// Start building an update that changes nothing, see [`UpdateTodoBuilder`]
+    pub fn builder() -> UpdateTodoBuilder {
+        UpdateTodoBuilder::default()
+    }
+}
+
+/// Fluent [`CreateTodo`], e.g. `CreateTodo::builder().title("x").notes("y").build()?`
+#[derive(Debug, Clone, Default)]
+pub struct CreateTodoBuilder {
+    todo: CreateTodo,
+}
+
+impl CreateTodoBuilder {
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.todo.title = title.into();
+        self
+    }
+
+    pub fn notes(mut self, notes: impl Into<String>) -> Self {
+        self.todo.notes = notes.into();
+        self
+    }
+
+    pub fn completed(mut self, completed: bool) -> Self {
+        self.todo.completed = completed;
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.todo.priority = Some(priority);
+        self
+    }
+
+    pub fn due_date(mut self, due_date: NaiveDate) -> Self {
+        self.todo.due_date = Some(due_date);
+        self
+    }
+
+    /// Finish the todo, failing like [`CreateTodo::validate`]
+    pub fn build(self) -> Result<CreateTodo, ValidationError> {
+        self.todo.validate()?;
+        Ok(self.todo)
+    }
+}
+
+/// Fluent [`UpdateTodo`], fields that are never set stay unchanged
+#[derive(Debug, Default)]
+pub struct UpdateTodoBuilder {
+    update: UpdateTodo,
+}
+
+impl UpdateTodoBuilder {
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.update.title = Some(title.into());
+        self
+    }
+
+    pub fn notes(mut self, notes: impl Into<String>) -> Self {
+        self.update.notes = Some(notes.into());
+        self
+    }
+
+    pub fn completed(mut self, completed: bool) -> Self {
+        self.update.completed = Some(completed);
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.update.priority = Some(priority);
+        self
+    }
+
+    /// Finish the update, failing like [`UpdateTodo::validate`]
+    pub fn build(self) -> Result<UpdateTodo, ValidationError> {
+        self.update.validate()?;
+        Ok(self.update)
+    }
+}
+
+/// Validate a deserialized import row, errors become report messages
+fn parse_create(row: serde_json::Result<CreateTodo>) -> Result<CreateTodo, String> {
+    let row = row.map_err(|err| err.to_string())?;
+    row.validate().map_err(|err| err.to_string())?;
+    Ok(row)
+}
+
+/// Row of a CSV import, only `title` is required
+///
+/// Empty or missing trailing fields mean no notes, open, normal priority and no due date.
+#[derive(Deserialize, Debug)]
+struct CsvTodo {
+    title: String,
     notes: Option<String>,
     completed: Option<bool>,
+    priority: Option<Priority>,
+    due_date: Option<NaiveDate>,
+}
+
+/// File format of [`TodoRepository::export`] and [`TodoRepository::import`]
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma separated values with a header row
+    Csv,
+    /// One JSON array
+    Json,
+    /// One JSON object per line
+    Jsonl,
+}
+
+/// Outcome of [`TodoRepository::import_csv`]
+///
+/// Every skipped row has an entry in `errors` with its line number and the reason.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: usize,
+    pub errors: Vec<(usize, String)>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct AssignTodo {
+    /// Person to assign, empty or omitted unassigns the todo
+    #[serde(default)]
+    pub assigned: String,
+}
+
+/// Body of a bulk priority change
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SetPriority {
+    pub ids: Vec<i64>,
+    pub priority: Priority,
+}
+
+/// Body of a bulk tag change, `add` and `remove` each apply to every todo in `ids`
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TagTodos {
+    pub ids: Vec<i64>,
+    #[serde(default)]
+    pub add: Option<String>,
+    #[serde(default)]
+    pub remove: Option<String>,
+}
+
+/// Outcome of [`TodoRepository::tag_many`]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TagChanges {
+    /// Todos that got the added tag
+    pub added: u64,
+    /// Todos that lost the removed tag
+    pub removed: u64,
+}
+
+/// One step of [`TodoRepository::batch`], e.g. `{"create": {...}}` or `{"delete": 2}`
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchOperation {
+    Create(CreateTodo),
+    Update(BatchUpdate),
+    Delete(i64),
+}
+
+/// Update of one todo inside a batch, `{"id": 1, "title": ...}`
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BatchUpdate {
+    pub id: i64,
+    #[serde(flatten)]
+    pub update: UpdateTodo,
+}
+
+/// Outcome of one [`BatchOperation`], in the order they were sent
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchOutcome {
+    Created(Todo),
+    /// The stored todo, also when nothing had to change
+    Updated(Todo),
+    Deleted(i64),
+    /// Only with `continue_on_error`, the operation changed nothing
+    Error(String),
+}
+
+/// Body of a batch, see [`TodoRepository::batch`]
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+/// Body of a reorder, every todo id once in the wanted order
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SetOrder {
+    pub order: Vec<i64>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SetNotes {
+    pub notes: String,
+}
+
+/// Connection pool usage
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    pub idle: usize,
+    pub size: u32,
+}
+
+/// What happened to a todo, see [`TodoRepository::subscribe`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoEventKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// Change of a single todo, sent after the change is committed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TodoEvent {
+    pub kind: TodoEventKind,
+    pub id: i64,
+}
+
+/// Kind of change asked for by [`TodoRepository::changes`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Todos created since the cutoff
+    Created,
+    /// Todos created before the cutoff and edited since
+    Updated,
+}
+
+/// Events a slow subscriber may fall behind unless `EVENT_CAPACITY` overrides it
+pub const DEFAULT_EVENT_CAPACITY: usize = 256;
+
+/// Event buffer size from `EVENT_CAPACITY`, read once per process and at least one
+///
+/// The buffer is shared by all subscribers and never grows. Once a subscriber is
+/// this many events behind, the oldest events are dropped for it and its next
+/// `recv` returns [`broadcast::error::RecvError::Lagged`] with the number missed.
+/// Senders never wait for slow subscribers.
+pub fn event_capacity() -> usize {
+    static CAPACITY: OnceLock<usize> = OnceLock::new();
+    *CAPACITY.get_or_init(|| event_capacity_from(std::env::var("EVENT_CAPACITY").ok()))
+}
+
+fn event_capacity_from(var: Option<String>) -> usize {
+    match var.as_deref().map(str::trim) {
+        None | Some("") => DEFAULT_EVENT_CAPACITY,
+        Some(value) => match value.parse::<usize>() {
+            Ok(capacity) => capacity.max(1),
+            Err(_) => {
+                tracing::warn!(
+                    "EVENT_CAPACITY={value} is not a number, using {DEFAULT_EVENT_CAPACITY}"
+                );
+                DEFAULT_EVENT_CAPACITY
+            }
+        },
+    }
+}
+
+/// Queries slower than this log a warning unless `SLOW_QUERY_MS` overrides it
+pub const DEFAULT_SLOW_QUERY_MS: u64 = 200;
+
+/// Slow query threshold from `SLOW_QUERY_MS`, read once per process
+fn slow_query_threshold() -> Duration {
+    static THRESHOLD: OnceLock<Duration> = OnceLock::new();
+    *THRESHOLD.get_or_init(|| slow_query_threshold_from(std::env::var("SLOW_QUERY_MS").ok()))
+}
+
+fn slow_query_threshold_from(var: Option<String>) -> Duration {
+    let ms = match var.as_deref().map(str::trim) {
+        None | Some("") => DEFAULT_SLOW_QUERY_MS,
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            tracing::warn!("SLOW_QUERY_MS={value} is not a number, using {DEFAULT_SLOW_QUERY_MS}");
+            DEFAULT_SLOW_QUERY_MS
+        }),
+    };
+    Duration::from_millis(ms)
+}
+
+/// Run a query and log how long it took
+async fn timed<T>(query: impl Future<Output = T>) -> T {
+    timed_with(slow_query_threshold(), query).await
+}
+
+/// Like [`timed`], warning with the calling method when `threshold` is exceeded
+async fn timed_with<T>(threshold: Duration, query: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let output = query.await;
+    let elapsed = start.elapsed();
+    let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+    if elapsed > threshold {
+        let method = tracing::Span::current()
+            .metadata()
+            .map_or("unknown", |metadata| metadata.name());
+        tracing::warn!(
+            method,
+            elapsed_ms,
+            threshold_ms = threshold.as_millis() as u64,
+            "slow query"
+        );
+    } else {
+        tracing::debug!(elapsed_ms, "query finished");
+    }
+    output
+}
+
+/// Table used unless [`TodoRepository::with_table`] picks another one
+pub const DEFAULT_TABLE: &str = "todos";
+
+/// Schema of a todo table, keep in sync with `migrations/`
+const TABLE_SCHEMA: &str = r#"CREATE TABLE IF NOT EXISTS {table}
+(
+    id          INTEGER PRIMARY KEY NOT NULL,
+    title       TEXT                NOT NULL,
+    notes       TEXT                NOT NULL DEFAULT 'note',
+    completed   BOOLEAN             NOT NULL DEFAULT 0,
+    assigned    TEXT                NOT NULL DEFAULT '',
+    priority    INTEGER             NOT NULL DEFAULT 1,
+    created_at  DATETIME            NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  DATETIME            NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    parent_id   INTEGER             REFERENCES {table} (id) ON DELETE CASCADE,
+    position    INTEGER             NOT NULL DEFAULT 0,
+    completed_at DATETIME,
+    due_date    DATE
+);"#;
+
+/// Tags of the todos in `{table}`, stored in `{table}_tags`
+const TAGS_SCHEMA: &str = r#"CREATE TABLE IF NOT EXISTS {table}_tags
+(
+    todo_id INTEGER NOT NULL REFERENCES {table} (id) ON DELETE CASCADE,
+    tag     TEXT    NOT NULL,
+    PRIMARY KEY (todo_id, tag)
+);"#;
+
+/// Last change of `{table}`, deletions included, kept in `{table}_modified` by triggers
+const MODIFIED_SCHEMA: &str = r#"CREATE TABLE IF NOT EXISTS {table}_modified
+(
+    id          INTEGER PRIMARY KEY NOT NULL CHECK (id = 1),
+    modified_at DATETIME            NOT NULL
+);
+CREATE TRIGGER IF NOT EXISTS {table}_modified_insert AFTER INSERT ON {table}
+BEGIN
+    INSERT INTO {table}_modified (id, modified_at)
+        SELECT 1, NEW.updated_at WHERE NOT EXISTS (SELECT 1 FROM {table}_modified);
+    UPDATE {table}_modified SET modified_at = MAX(modified_at, NEW.updated_at) WHERE id = 1;
+END;
+CREATE TRIGGER IF NOT EXISTS {table}_modified_update AFTER UPDATE ON {table}
+BEGIN
+    INSERT INTO {table}_modified (id, modified_at)
+        SELECT 1, NEW.updated_at WHERE NOT EXISTS (SELECT 1 FROM {table}_modified);
+    UPDATE {table}_modified SET modified_at = MAX(modified_at, NEW.updated_at) WHERE id = 1;
+END;
+CREATE TRIGGER IF NOT EXISTS {table}_modified_delete AFTER DELETE ON {table}
+BEGIN
+    INSERT INTO {table}_modified (id, modified_at)
+        SELECT 1, CURRENT_TIMESTAMP WHERE NOT EXISTS (SELECT 1 FROM {table}_modified);
+    UPDATE {table}_modified SET modified_at = MAX(modified_at, CURRENT_TIMESTAMP) WHERE id = 1;
+END;"#;
+
+/// Longest tag accepted
+pub const MAX_TAG_LEN: usize = 50;
+
+/// Check `tag` is worth storing and return it trimmed
+fn validate_tag(tag: &str) -> Result<&str> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(RepositoryError::Validation(
+            "tag must not be empty".to_owned(),
+        ));
+    }
+    if tag.chars().count() > MAX_TAG_LEN {
+        return Err(RepositoryError::Validation(format!(
+            "tag must be at most {MAX_TAG_LEN} characters"
+        )));
+    }
+    Ok(tag)
+}
+
+/// Check `name` can be interpolated into SQL as a table name
+fn validate_table_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid_start = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    if !valid_start || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(RepositoryError::Validation(format!(
+            "invalid table name `{name}`, use letters, digits and underscores only"
+        )));
+    }
+    Ok(())
+}
+
+/// Write `todos` in the given export format
+///
+/// `header` only matters for CSV, without it the column names are left out so rows
+/// can be appended to an existing file.
+pub fn write_todos(
+    format: ExportFormat,
+    header: bool,
+    todos: &[Todo],
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    match format {
+        ExportFormat::Csv => {
+            let mut csv = csv::WriterBuilder::new()
+                .has_headers(header)
+                .from_writer(&mut writer);
+            for todo in todos {
+                csv.serialize(todo).map_err(std::io::Error::from)?;
+            }
+            csv.flush()?;
+        }
+        ExportFormat::Json => {
+            serde_json::to_writer(&mut writer, todos).map_err(std::io::Error::from)?;
+            writeln!(writer)?;
+        }
+        ExportFormat::Jsonl => {
+            for todo in todos {
+                serde_json::to_writer(&mut writer, todo).map_err(std::io::Error::from)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Call `f` with every page of `per_page` todos in [`TodoRepository::list`] order
+///
+/// Stops after the first page shorter than `per_page`, empty pages are skipped.
+/// Every todo is visited once as long as nobody inserts or deletes todos meanwhile.
+pub async fn for_each_page<F>(repo: &mut TodoRepository, per_page: u32, mut f: F) -> Result<()>
+where
+    F: FnMut(Vec<Todo>),
+{
+    if per_page == 0 {
+        return Err(RepositoryError::Validation(
+            "per_page must be at least 1".to_owned(),
+        ));
+    }
+    let mut offset = 0u32;
+    loop {
+        let todos = repo
+            .list(Pagination::new(Some(offset), Some(per_page)))
+            .await?;
+        let last = todos.len() < per_page as usize;
+        if !todos.is_empty() {
+            f(todos);
+        }
+        match offset.checked_add(per_page) {
+            Some(next) if !last => offset = next,
+            _ => return Ok(()),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct TodoRepository {
     pool: SqlitePool,
+    table: String,
+    events: broadcast::Sender<TodoEvent>,
 }
 
 impl TodoRepository {
     pub fn new(pool: SqlitePool) -> TodoRepository {
-        TodoRepository { pool }
+        TodoRepository {
+            pool,
+            table: DEFAULT_TABLE.to_owned(),
+            events: broadcast::channel(event_capacity()).0,
+        }
+    }
+    /// Buffer at most `capacity` events per subscriber instead of [`event_capacity`]
+    ///
+    /// Replaces the event channel, existing subscribers stop receiving events.
+    pub fn with_event_capacity(mut self, capacity: usize) -> TodoRepository {
+        self.events = broadcast::channel(capacity.max(1)).0;
+        self
+    }
+    /// Receive a [`TodoEvent`] for every change made through this repository or its clones
+    pub fn subscribe(&self) -> broadcast::Receiver<TodoEvent> {
+        self.events.subscribe()
     }
-    
-    // Create new todo
+    /// Tell subscribers about a change, nothing happens without subscribers
+    fn notify(&self, kind: TodoEventKind, ids: impl IntoIterator<Item = i64>) {
+        if self.events.receiver_count() == 0 {
+            return;
+        }
+        for id in ids {
+            let _ = self.events.send(TodoEvent { kind, id });
+        }
+    }
+    /// Store todos in `table` instead of the default `todos`
+    ///
+    /// Only letters, digits and underscores are accepted since the name ends up in SQL.
+    pub fn with_table(mut self, table: &str) -> Result<TodoRepository> {
+        validate_table_name(table)?;
+        self.table = table.to_owned();
+        Ok(self)
+    }
+    /// Name of the table todos are stored in
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+    /// Connection pool shared by this repository and its clones
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+    /// Close the pool once every connection is returned, later queries fail
+    ///
+    /// Clones share the pool, so this closes them as well.
+    pub async fn close(self) {
+        self.pool.close().await;
+    }
+    /// Create the todo table if missing, for tables not managed by migrations
+    #[tracing::instrument(skip(self), fields(table = %self.table))]
+    pub async fn create_table(&mut self) -> Result<()> {
+        for schema in [TABLE_SCHEMA, TAGS_SCHEMA, MODIFIED_SCHEMA] {
+            let query = schema.replace("{table}", &self.table);
+            timed(sqlx::query(&query).execute(&self.pool)).await?;
+        }
+        Ok(())
+    }
+    /// Create repository on top of a fresh in-memory database with migrations applied
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn in_memory() -> anyhow::Result<TodoRepository> {
+        TodoRepository::in_memory_with(&[]).await
+    }
+    /// Create in-memory repository and seed it with the given todos
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn in_memory_with(seed: &[CreateTodo]) -> anyhow::Result<TodoRepository> {
+        let pool = crate::db::connect("sqlite::memory:").await?;
+        crate::db::migrate(&pool).await?;
+        let mut repo = TodoRepository::new(pool);
+        for todo in seed {
+            repo.create(todo.clone()).await?;
+        }
+        Ok(repo)
+    }
+
+    /// Current connection pool usage
+    pub fn pool_stats(&self) -> PoolStats {
+        PoolStats {
+            idle: self.pool.num_idle(),
+            size: self.pool.size(),
+        }
+    }
+    /// Check the database answers, waiting at most `timeout` for a free connection
+    #[tracing::instrument(skip(self))]
+    pub async fn ping(&mut self, timeout: Duration) -> Result<()> {
+        let mut conn = tokio::time::timeout(timeout, self.pool.acquire())
+            .await
+            .map_err(|_| sqlx::Error::PoolTimedOut)??;
+        timed(sqlx::query("SELECT 1").execute(&mut *conn)).await?;
+        Ok(())
+    }
+    // Create new todo, the title is stored normalized by normalize_title
+    #[tracing::instrument(skip(self, todo))]
     pub async fn create(&mut self, todo: CreateTodo) -> Result<i64> {
-        let id = sqlx::query("INSERT INTO todos ( title ) VALUES ( ?1 )")
-            .bind(todo.title)
-            .execute(&self.pool)
+        let id = timed(
+            sqlx::query(&format!(
+                "INSERT INTO {table} ( title, notes, completed, priority, completed_at, due_date ) VALUES ( ?1, ?2, ?3, ?4, CASE WHEN ?3 THEN CURRENT_TIMESTAMP END, ?5 )",
+                table = self.table
+            ))
+            .bind(normalize_title(&todo.title))
+            .bind(todo.notes)
+            .bind(todo.completed)
+            .bind(todo.priority.unwrap_or_default())
+            .bind(todo.due_date)
+            .execute(&self.pool),
+        )
+        .await?
+        .last_insert_rowid();
+        self.notify(TodoEventKind::Created, [id]);
+        Ok(id)
+    }
+    /// Create todo unless one with the same title exists, `None` for a duplicate
+    ///
+    /// Only the unique index of [`crate::db::set_unique_titles`] (`UNIQUE_TITLES`)
+    /// stops double submits racing on two connections, `INSERT OR IGNORE` then skips
+    /// the loser. Without it the `NOT EXISTS` check alone guards against duplicates.
+    #[tracing::instrument(skip(self, todo))]
+    pub async fn create_unique(&mut self, todo: CreateTodo) -> Result<Option<i64>> {
+        let query = format!(
+            r#"INSERT OR IGNORE INTO {table} ( title, notes, completed, priority, completed_at, due_date )
+            SELECT ?1, ?2, ?3, ?4, CASE WHEN ?3 THEN CURRENT_TIMESTAMP END, ?5 WHERE NOT EXISTS (SELECT 1 FROM {table} WHERE title = ?1)"#,
+            table = self.table
+        );
+        let result = timed(
+            sqlx::query(&query)
+                .bind(normalize_title(&todo.title))
+                .bind(todo.notes)
+                .bind(todo.completed)
+                .bind(todo.priority.unwrap_or_default())
+                .bind(todo.due_date)
+                .execute(&self.pool),
+        )
+        .await?;
+        let id = (result.rows_affected() > 0).then(|| result.last_insert_rowid());
+        self.notify(TodoEventKind::Created, id);
+        Ok(id)
+    }
+    /// Create several todos in one transaction, returning their ids in order
+    #[tracing::instrument(skip(self, todos), fields(count = todos.len()))]
+    pub async fn create_many(&mut self, todos: &[CreateTodo]) -> Result<Vec<i64>> {
+        let mut tx = self.pool.begin().await?;
+        let mut ids = Vec::with_capacity(todos.len());
+        for todo in todos {
+            let id = timed(
+                sqlx::query(&format!(
+                    "INSERT INTO {table} ( title, notes, completed, priority, completed_at, due_date ) VALUES ( ?1, ?2, ?3, ?4, CASE WHEN ?3 THEN CURRENT_TIMESTAMP END, ?5 )",
+                    table = self.table
+                ))
+                .bind(normalize_title(&todo.title))
+                .bind(&todo.notes)
+                .bind(todo.completed)
+                .bind(todo.priority.unwrap_or_default())
+                .bind(todo.due_date)
+                .execute(&mut *tx),
+            )
             .await?
             .last_insert_rowid();
-        Ok(id)
+            ids.push(id);
+        }
+        tx.commit().await?;
+        self.notify(TodoEventKind::Created, ids.iter().copied());
+        Ok(ids)
+    }
+    /// Number of todos
+    #[tracing::instrument(skip(self))]
+    pub async fn count(&mut self) -> Result<i64> {
+        let count: i64 = timed(
+            sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table}", table = self.table))
+                .fetch_one(&self.pool),
+        )
+        .await?;
+        Ok(count)
+    }
+    /// When todos were last stored or deleted, `None` before the first one
+    ///
+    /// Kept by triggers: the newest `updated_at` written or the time of a delete.
+    #[tracing::instrument(skip(self))]
+    pub async fn last_modified(&mut self) -> Result<Option<NaiveDateTime>> {
+        let last: Option<NaiveDateTime> = timed(
+            sqlx::query_scalar(&format!(
+                "SELECT MAX(modified_at) FROM {table}_modified",
+                table = self.table
+            ))
+            .fetch_one(&self.pool),
+        )
+        .await?;
+        Ok(last)
+    }
+    /// Import todos from CSV with a `title,notes,completed,priority` header
+    ///
+    /// Only `title` is required. Rows that fail to parse or validate are skipped and
+    /// reported, the remaining rows are inserted in one transaction.
+    #[tracing::instrument(skip(self, reader))]
+    pub async fn import_csv(&mut self, reader: impl Read) -> Result<ImportReport> {
+        let mut csv = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .from_reader(reader);
+        let invalid = |err: csv::Error| RepositoryError::Validation(format!("invalid CSV: {err}"));
+        let headers = csv.headers().map_err(invalid)?.clone();
+        let mut report = ImportReport::default();
+        let mut rows = Vec::new();
+        for record in csv.records() {
+            let mut record = record.map_err(invalid)?;
+            while record.len() < headers.len() {
+                record.push_field("");
+            }
+            let line = record
+                .position()
+                .map_or(0, |position| position.line() as usize);
+            let row = record
+                .deserialize::<CsvTodo>(Some(&headers))
+                .map_err(|err| err.to_string())
+                .and_then(|row| {
+                    let create = CreateTodo {
+                        title: row.title,
+                        notes: row.notes.unwrap_or_default(),
+                        completed: row.completed.unwrap_or_default(),
+                        priority: row.priority,
+                        due_date: row.due_date,
+                    };
+                    create.validate().map_err(|err| err.to_string())?;
+                    Ok(create)
+                });
+            match row {
+                Ok(row) => rows.push((line, row)),
+                Err(err) => {
+                    report.skipped += 1;
+                    report.errors.push((line, err));
+                }
+            }
+        }
+        self.insert_rows(rows, report).await
+    }
+    /// Import todos from a JSON array of objects shaped like [`CreateTodo`]
+    ///
+    /// Exported todos import as well, their other fields are ignored. Invalid items
+    /// are skipped and reported with their 1-based position in the array.
+    #[tracing::instrument(skip(self, reader))]
+    pub async fn import_json(&mut self, reader: impl Read) -> Result<ImportReport> {
+        let items: Vec<serde_json::Value> = serde_json::from_reader(reader)
+            .map_err(|err| RepositoryError::Validation(format!("invalid JSON: {err}")))?;
+        let mut report = ImportReport::default();
+        let mut rows = Vec::new();
+        for (index, item) in items.into_iter().enumerate() {
+            match parse_create(serde_json::from_value(item)) {
+                Ok(row) => rows.push((index + 1, row)),
+                Err(err) => {
+                    report.skipped += 1;
+                    report.errors.push((index + 1, err));
+                }
+            }
+        }
+        self.insert_rows(rows, report).await
+    }
+    /// Import todos from JSON lines, one object shaped like [`CreateTodo`] per line
+    ///
+    /// Blank lines are ignored, invalid lines are skipped and reported.
+    #[tracing::instrument(skip(self, reader))]
+    pub async fn import_jsonl(&mut self, reader: impl Read) -> Result<ImportReport> {
+        let mut report = ImportReport::default();
+        let mut rows = Vec::new();
+        for (index, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_create(serde_json::from_str(&line)) {
+                Ok(row) => rows.push((index + 1, row)),
+                Err(err) => {
+                    report.skipped += 1;
+                    report.errors.push((index + 1, err));
+                }
+            }
+        }
+        self.insert_rows(rows, report).await
+    }
+    /// Import todos in the given format, see the `import_*` methods
+    pub async fn import(
+        &mut self,
+        format: ExportFormat,
+        reader: impl Read,
+    ) -> Result<ImportReport> {
+        match format {
+            ExportFormat::Csv => self.import_csv(reader).await,
+            ExportFormat::Json => self.import_json(reader).await,
+            ExportFormat::Jsonl => self.import_jsonl(reader).await,
+        }
+    }
+    /// Insert parsed import rows in one transaction, adding failures to `report`
+    async fn insert_rows(
+        &mut self,
+        rows: Vec<(usize, CreateTodo)>,
+        mut report: ImportReport,
+    ) -> Result<ImportReport> {
+        let query = format!(
+            "INSERT INTO {table} ( title, notes, completed, priority, completed_at, due_date ) VALUES ( ?1, ?2, ?3, ?4, CASE WHEN ?3 THEN CURRENT_TIMESTAMP END, ?5 )",
+            table = self.table
+        );
+        let mut tx = self.pool.begin().await?;
+        let mut ids = Vec::new();
+        for (line, row) in rows {
+            let inserted = timed(
+                sqlx::query(&query)
+                    .bind(normalize_title(&row.title))
+                    .bind(row.notes)
+                    .bind(row.completed)
+                    .bind(row.priority.unwrap_or_default())
+                    .bind(row.due_date)
+                    .execute(&mut *tx),
+            )
+            .await;
+            match inserted {
+                Ok(result) => {
+                    report.imported += 1;
+                    ids.push(result.last_insert_rowid());
+                }
+                Err(err) => {
+                    report.skipped += 1;
+                    report.errors.push((line, err.to_string()));
+                }
+            }
+        }
+        tx.commit().await?;
+        self.notify(TodoEventKind::Created, ids);
+        Ok(report)
+    }
+    /// Write every todo ordered by id to `writer`, returning how many were written
+    ///
+    /// See [`write_todos`] for `header`.
+    #[tracing::instrument(skip(self, writer))]
+    pub async fn export(
+        &mut self,
+        format: ExportFormat,
+        header: bool,
+        mut writer: impl Write,
+    ) -> Result<usize> {
+        let todos = self.list_sorted(Pagination::all(), SortBy::Id).await?;
+        write_todos(format, header, &todos, &mut writer)?;
+        writer.flush()?;
+        Ok(todos.len())
+    }
+    /// Count `(open, completed)` todos in one query
+    #[tracing::instrument(skip(self))]
+    pub async fn count_by_completion(&mut self) -> Result<(i64, i64)> {
+        let rows: Vec<(bool, i64)> = timed(
+            sqlx::query_as(&format!(
+                "SELECT completed, COUNT(*) FROM {table} GROUP BY completed",
+                table = self.table
+            ))
+            .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(rows
+            .into_iter()
+            .fold((0, 0), |(open, done), (completed, count)| {
+                if completed {
+                    (open, done + count)
+                } else {
+                    (open + count, done)
+                }
+            }))
+    }
+    /// Count todos in total, by completion and by priority in one query
+    ///
+    /// Overdue todos are counted against the current UTC day.
+    pub async fn stats(&mut self) -> Result<TodoStats> {
+        self.stats_on(Utc::now().date_naive()).await
+    }
+    /// Like [`TodoRepository::stats`], counting todos due before `today` as overdue
+    #[tracing::instrument(skip(self))]
+    pub async fn stats_on(&mut self, today: NaiveDate) -> Result<TodoStats> {
+        let rows: Vec<(bool, Priority, i64)> = timed(
+            sqlx::query_as(&format!(
+                "SELECT completed, priority, COUNT(*) FROM {table} GROUP BY completed, priority",
+                table = self.table
+            ))
+            .fetch_all(&self.pool),
+        )
+        .await?;
+        let mut stats = TodoStats {
+            total: 0,
+            open: 0,
+            completed: 0,
+            overdue: self.count_overdue(today).await?,
+            by_priority: Priority::ALL
+                .into_iter()
+                .map(|priority| (priority, 0))
+                .collect(),
+        };
+        for (completed, priority, count) in rows {
+            stats.total += count;
+            if completed {
+                stats.completed += count;
+            } else {
+                stats.open += count;
+            }
+            *stats.by_priority.entry(priority).or_default() += count;
+        }
+        Ok(stats)
+    }
+    /// Move the due date of every open todo that has one `days` days later, returning how many moved
+    ///
+    /// Negative `days` move due dates earlier, completed todos keep theirs.
+    #[tracing::instrument(skip(self))]
+    pub async fn shift_due_dates(&mut self, days: i64) -> Result<u64> {
+        let ids: Vec<i64> = timed(
+            sqlx::query_scalar(&format!(
+                r#"UPDATE {table} SET due_date = date(due_date, ?1 || ' days'), updated_at = CURRENT_TIMESTAMP
+                WHERE completed = 0 AND due_date IS NOT NULL
+                RETURNING id"#,
+                table = self.table
+            ))
+            .bind(format!("{days:+}"))
+            .fetch_all(&self.pool),
+        )
+        .await?;
+        self.notify(TodoEventKind::Updated, ids.iter().copied());
+        Ok(ids.len() as u64)
+    }
+    /// Whether every todo is completed, `false` for an empty table since nothing got done
+    #[tracing::instrument(skip(self))]
+    pub async fn all_completed(&mut self) -> Result<bool> {
+        let done = timed(
+            sqlx::query_scalar(&format!(
+                "SELECT EXISTS (SELECT 1 FROM {table}) AND NOT EXISTS (SELECT 1 FROM {table} WHERE completed = 0)",
+                table = self.table
+            ))
+            .fetch_one(&self.pool),
+        )
+        .await?;
+        Ok(done)
+    }
+    /// Number of open todos due before `today`, todos due `today` are not overdue yet
+    #[tracing::instrument(skip(self))]
+    pub async fn count_overdue(&mut self, today: NaiveDate) -> Result<i64> {
+        let count = timed(
+            sqlx::query_scalar(&format!(
+                "SELECT COUNT(*) FROM {table} WHERE completed = 0 AND due_date < ?1",
+                table = self.table
+            ))
+            .bind(today)
+            .fetch_one(&self.pool),
+        )
+        .await?;
+        Ok(count)
+    }
+    /// Get list of todos support pagination.
+    ///
+    /// Most urgent todos come first, see [`SortBy::Priority`].
+    #[tracing::instrument(
+        skip(self, pagination),
+        fields(offset = pagination.offset, limit = pagination.limit)
+    )]
+    pub async fn list(&mut self, pagination: Pagination) -> Result<Vec<Todo>> {
+        self.list_sorted(pagination, SortBy::default()).await
+    }
+    /// Get list of todos in the given order, support pagination.
+    #[tracing::instrument(
+        skip(self, pagination),
+        fields(offset = pagination.offset, limit = pagination.limit)
+    )]
+    pub async fn list_sorted(&mut self, pagination: Pagination, sort: SortBy) -> Result<Vec<Todo>> {
+        self.list_ordered(pagination, sort, false).await
+    }
+    /// Like [`TodoRepository::list_sorted`], last todo first when `reverse` is set
+    #[tracing::instrument(
+        skip(self, pagination),
+        fields(offset = pagination.offset, limit = pagination.limit)
+    )]
+    pub async fn list_ordered(
+        &mut self,
+        pagination: Pagination,
+        sort: SortBy,
+        reverse: bool,
+    ) -> Result<Vec<Todo>> {
+        let order_by = if reverse {
+            sort.order_by_reversed()
+        } else {
+            sort.order_by()
+        };
+        let query = format!(
+            "SELECT * FROM {} ORDER BY {} LIMIT ?1 OFFSET ?2;",
+            self.table, order_by
+        );
+        let todos: Vec<Todo> = timed(
+            sqlx::query_as(&query)
+                .bind(pagination.limit())
+                .bind(pagination.offset())
+                .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(todos)
+    }
+    /// Like [`TodoRepository::list_sorted`], selecting only `fields` as JSON objects
+    ///
+    /// Keys follow the order of `fields`, an empty slice selects every field.
+    #[tracing::instrument(
+        skip(self, pagination),
+        fields(offset = pagination.offset, limit = pagination.limit)
+    )]
+    pub async fn list_fields(
+        &mut self,
+        pagination: Pagination,
+        sort: SortBy,
+        fields: &[TodoField],
+    ) -> Result<Vec<serde_json::Map<String, serde_json::Value>>> {
+        let fields = if fields.is_empty() {
+            &TodoField::ALL[..]
+        } else {
+            fields
+        };
+        let columns: Vec<&str> = fields.iter().map(|field| field.column()).collect();
+        let query = format!(
+            "SELECT {columns} FROM {table} ORDER BY {order_by} LIMIT ?1 OFFSET ?2",
+            columns = columns.join(", "),
+            table = self.table,
+            order_by = sort.order_by()
+        );
+        let rows = timed(
+            sqlx::query(&query)
+                .bind(pagination.limit())
+                .bind(pagination.offset())
+                .fetch_all(&self.pool),
+        )
+        .await?;
+        rows.iter()
+            .map(|row| {
+                fields
+                    .iter()
+                    .map(|field| Ok((field.column().to_owned(), field.value(row)?)))
+                    .collect()
+            })
+            .collect()
+    }
+    /// Todos created in `[since, until)` ordered by creation, open ended where `None`
+    #[tracing::instrument(skip(self, pagination))]
+    pub async fn list_created_between(
+        &mut self,
+        since: Option<NaiveDateTime>,
+        until: Option<NaiveDateTime>,
+        pagination: Pagination,
+    ) -> Result<Vec<Todo>> {
+        let query = format!(
+            r#"SELECT * FROM {table}
+            WHERE (?1 IS NULL OR created_at >= ?1) AND (?2 IS NULL OR created_at < ?2)
+            ORDER BY created_at ASC, id ASC LIMIT ?3 OFFSET ?4"#,
+            table = self.table
+        );
+        let todos: Vec<Todo> = timed(
+            sqlx::query_as(&query)
+                .bind(since)
+                .bind(until)
+                .bind(pagination.limit())
+                .bind(pagination.offset())
+                .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(todos)
+    }
+    /// Number of todos [`TodoRepository::list_created_between`] would return without pagination
+    #[tracing::instrument(skip(self))]
+    pub async fn count_created_between(
+        &mut self,
+        since: Option<NaiveDateTime>,
+        until: Option<NaiveDateTime>,
+    ) -> Result<i64> {
+        let count = timed(
+            sqlx::query_scalar(&format!(
+                "SELECT COUNT(*) FROM {table} WHERE (?1 IS NULL OR created_at >= ?1) AND (?2 IS NULL OR created_at < ?2)",
+                table = self.table
+            ))
+            .bind(since)
+            .bind(until)
+            .fetch_one(&self.pool),
+        )
+        .await?;
+        Ok(count)
+    }
+    /// Todos with `min <= id <= max` ordered by id
+    #[tracing::instrument(skip(self))]
+    pub async fn list_id_range(&mut self, min: i64, max: i64) -> Result<Vec<Todo>> {
+        let todos: Vec<Todo> = timed(
+            sqlx::query_as(&format!(
+                "SELECT * FROM {table} WHERE id BETWEEN ?1 AND ?2 ORDER BY id",
+                table = self.table
+            ))
+            .bind(min)
+            .bind(max)
+            .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(todos)
+    }
+    /// Todos changed at or after `since`, least recently changed first
+    ///
+    /// `kind` narrows the feed to creations or to edits of older todos, `None`
+    /// returns both. Deleted todos are not reported.
+    #[tracing::instrument(skip(self, pagination))]
+    pub async fn changes(
+        &mut self,
+        since: NaiveDateTime,
+        kind: Option<ChangeKind>,
+        pagination: Pagination,
+    ) -> Result<Vec<Todo>> {
+        let filter = match kind {
+            None => "updated_at >= ?1",
+            Some(ChangeKind::Created) => "created_at >= ?1",
+            Some(ChangeKind::Updated) => "updated_at >= ?1 AND created_at < ?1",
+        };
+        let query = format!(
+            "SELECT * FROM {table} WHERE {filter} ORDER BY updated_at ASC, id ASC LIMIT ?2 OFFSET ?3",
+            table = self.table
+        );
+        let todos: Vec<Todo> = timed(
+            sqlx::query_as(&query)
+                .bind(since)
+                .bind(pagination.limit())
+                .bind(pagination.offset())
+                .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(todos)
+    }
+    /// 1-based `page` of `per_page` todos, most urgent first, with the total count
+    ///
+    /// Both queries run in one transaction so `total` matches `items`.
+    #[tracing::instrument(skip(self))]
+    pub async fn page(&mut self, page: u32, per_page: u32) -> Result<Page<Todo>> {
+        if page == 0 || per_page == 0 {
+            return Err(RepositoryError::Validation(
+                "page and per_page start at 1".to_owned(),
+            ));
+        }
+        let offset = u64::from(page - 1) * u64::from(per_page);
+        let mut tx = self.pool.begin().await?;
+        let total: i64 = timed(
+            sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table}", table = self.table))
+                .fetch_one(&mut *tx),
+        )
+        .await?;
+        let query = format!(
+            "SELECT * FROM {} ORDER BY {} LIMIT ?1 OFFSET ?2;",
+            self.table,
+            SortBy::default().order_by()
+        );
+        let items: Vec<Todo> = timed(
+            sqlx::query_as(&query)
+                .bind(per_page)
+                .bind(offset as i64)
+                .fetch_all(&mut *tx),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(Page {
+            items,
+            total,
+            page,
+            per_page,
+        })
+    }
+    /// Get todo from id
+    #[tracing::instrument(skip(self))]
+    pub async fn get(&mut self, id: i64) -> Result<Todo> {
+        let todo: Option<Todo> = timed(
+            sqlx::query_as(&format!(
+                "select * from {table} where id = ?1 limit 1",
+                table = self.table
+            ))
+            .bind(id)
+            .fetch_optional(&self.pool),
+        )
+        .await?;
+        todo.ok_or(RepositoryError::NotFound(id))
+    }
+    /// Todo titled exactly `title`, the oldest one when several share it
+    #[tracing::instrument(skip(self))]
+    pub async fn find_by_title(&mut self, title: &str) -> Result<Option<Todo>> {
+        let todo: Option<Todo> = timed(
+            sqlx::query_as(&format!(
+                "select * from {table} where title = ?1 order by id limit 1",
+                table = self.table
+            ))
+            .bind(title)
+            .fetch_optional(&self.pool),
+        )
+        .await?;
+        Ok(todo)
+    }
+    /// Oldest open todo, `None` when everything is done
+    #[tracing::instrument(skip(self))]
+    pub async fn first_open(&mut self) -> Result<Option<Todo>> {
+        let todo: Option<Todo> = timed(
+            sqlx::query_as(&format!(
+                "SELECT * FROM {table} WHERE completed = 0 ORDER BY id LIMIT 1",
+                table = self.table
+            ))
+            .fetch_optional(&self.pool),
+        )
+        .await?;
+        Ok(todo)
+    }
+    /// Random open todo, `None` when everything is done
+    #[tracing::instrument(skip(self))]
+    pub async fn random_open(&mut self) -> Result<Option<Todo>> {
+        let todo: Option<Todo> = timed(
+            sqlx::query_as(&format!(
+                "SELECT * FROM {table} WHERE completed = 0 ORDER BY RANDOM() LIMIT 1",
+                table = self.table
+            ))
+            .fetch_optional(&self.pool),
+        )
+        .await?;
+        Ok(todo)
+    }
+    /// Most recently created todo, `None` on an empty table
+    #[tracing::instrument(skip(self))]
+    pub async fn latest(&mut self) -> Result<Option<Todo>> {
+        let todo: Option<Todo> = timed(
+            sqlx::query_as(&format!(
+                "SELECT * FROM {table} ORDER BY id DESC LIMIT 1",
+                table = self.table
+            ))
+            .fetch_optional(&self.pool),
+        )
+        .await?;
+        Ok(todo)
+    }
+    /// Get todos with the given ids ordered by id, unknown ids are skipped
+    #[tracing::instrument(skip(self))]
+    pub async fn get_many(&mut self, ids: &[i64]) -> Result<Vec<Todo>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut query = sqlx::QueryBuilder::new(&format!(
+            "SELECT * FROM {table} WHERE id IN (",
+            table = self.table
+        ));
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(id);
+        }
+        separated.push_unseparated(") ORDER BY id");
+        let todos: Vec<Todo> = timed(query.build_query_as().fetch_all(&self.pool)).await?;
+        Ok(todos)
+    }
+    /// [`TodoRepository::get_many`] keyed by id, unknown ids are absent
+    #[tracing::instrument(skip(self))]
+    pub async fn get_map(&mut self, ids: &[i64]) -> Result<HashMap<i64, Todo>> {
+        let todos = self.get_many(ids).await?;
+        Ok(todos.into_iter().map(|todo| (todo.id, todo)).collect())
+    }
+    /// Set `priority` on every todo in `ids` in one statement, returning how many changed
+    ///
+    /// Todos that already have `priority` are left alone, unknown ids are skipped.
+    #[tracing::instrument(skip(self))]
+    pub async fn set_priority_many(&mut self, ids: &[i64], priority: Priority) -> Result<u64> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let mut query = sqlx::QueryBuilder::new(&format!(
+            "UPDATE {table} SET updated_at = CURRENT_TIMESTAMP, priority = ",
+            table = self.table
+        ));
+        query.push_bind(priority);
+        query.push(" WHERE priority <> ");
+        query.push_bind(priority);
+        query.push(" AND id IN (");
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(id);
+        }
+        separated.push_unseparated(") RETURNING id");
+        let ids: Vec<i64> = timed(query.build_query_scalar().fetch_all(&self.pool)).await?;
+        self.notify(TodoEventKind::Updated, ids.iter().copied());
+        Ok(ids.len() as u64)
+    }
+    /// Tag every todo in `ids` with `tag`, returning how many didn't have it yet
+    ///
+    /// Unknown ids are skipped.
+    pub async fn add_tag_many(&mut self, ids: &[i64], tag: &str) -> Result<u64> {
+        Ok(self.tag_many(ids, Some(tag), None).await?.added)
+    }
+    /// Remove `tag` from every todo in `ids`, returning how many had it
+    pub async fn remove_tag_many(&mut self, ids: &[i64], tag: &str) -> Result<u64> {
+        Ok(self.tag_many(ids, None, Some(tag)).await?.removed)
+    }
+    /// Add `add` to and then remove `remove` from every todo in `ids` in one transaction
+    ///
+    /// Both tags are validated before anything changes, unknown ids are skipped.
+    #[tracing::instrument(skip(self))]
+    pub async fn tag_many(
+        &mut self,
+        ids: &[i64],
+        add: Option<&str>,
+        remove: Option<&str>,
+    ) -> Result<TagChanges> {
+        let add = add.map(validate_tag).transpose()?;
+        let remove = remove.map(validate_tag).transpose()?;
+        if ids.is_empty() {
+            return Ok(TagChanges::default());
+        }
+        let mut tx = self.pool.begin().await?;
+        let mut tagged: Vec<i64> = Vec::new();
+        if let Some(tag) = add {
+            let mut query = sqlx::QueryBuilder::new(&format!(
+                "INSERT OR IGNORE INTO {table}_tags (todo_id, tag) SELECT id, ",
+                table = self.table
+            ));
+            query.push_bind(tag);
+            query.push(format!(" FROM {table} WHERE id IN (", table = self.table));
+            let mut separated = query.separated(", ");
+            for id in ids {
+                separated.push_bind(id);
+            }
+            separated.push_unseparated(") RETURNING todo_id");
+            tagged = timed(query.build_query_scalar().fetch_all(&mut *tx)).await?;
+        }
+        let mut untagged: Vec<i64> = Vec::new();
+        if let Some(tag) = remove {
+            let mut query = sqlx::QueryBuilder::new(&format!(
+                "DELETE FROM {table}_tags WHERE tag = ",
+                table = self.table
+            ));
+            query.push_bind(tag);
+            query.push(" AND todo_id IN (");
+            let mut separated = query.separated(", ");
+            for id in ids {
+                separated.push_bind(id);
+            }
+            separated.push_unseparated(") RETURNING todo_id");
+            untagged = timed(query.build_query_scalar().fetch_all(&mut *tx)).await?;
+        }
+        tx.commit().await?;
+        let changed: BTreeSet<i64> = tagged.iter().chain(&untagged).copied().collect();
+        self.notify(TodoEventKind::Updated, changed);
+        Ok(TagChanges {
+            added: tagged.len() as u64,
+            removed: untagged.len() as u64,
+        })
+    }
+    /// Todos tagged with `tag` in creation order
+    #[tracing::instrument(skip(self))]
+    pub async fn list_by_tag(&mut self, tag: &str) -> Result<Vec<Todo>> {
+        let query = format!(
+            r#"SELECT {table}.* FROM {table}
+            JOIN {table}_tags ON {table}_tags.todo_id = {table}.id
+            WHERE {table}_tags.tag = ?1
+            ORDER BY {table}.id"#,
+            table = self.table
+        );
+        let todos: Vec<Todo> = timed(
+            sqlx::query_as(&query)
+                .bind(tag.trim())
+                .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(todos)
+    }
+    /// Tags of todo `id` in alphabetical order, empty for unknown ids
+    #[tracing::instrument(skip(self))]
+    pub async fn tags(&mut self, id: i64) -> Result<Vec<String>> {
+        let tags: Vec<String> = timed(
+            sqlx::query_scalar(&format!(
+                "SELECT tag FROM {table}_tags WHERE todo_id = ?1 ORDER BY tag",
+                table = self.table
+            ))
+            .bind(id)
+            .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(tags)
+    }
+    /// Direct subtasks of todo `id` ordered by position
+    #[tracing::instrument(skip(self))]
+    pub async fn children(&mut self, id: i64) -> Result<Vec<Todo>> {
+        let todos: Vec<Todo> = timed(
+            sqlx::query_as(&format!(
+                "SELECT * FROM {table} WHERE parent_id = ?1 ORDER BY position, id",
+                table = self.table
+            ))
+            .bind(id)
+            .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(todos)
+    }
+    /// Update todo and return the stored row
+    ///
+    /// Returns `None` without touching the row when every field already has the
+    /// requested value, [`RepositoryError::NotFound`] when there is no such todo.
+    #[tracing::instrument(skip(self, update))]
+    pub async fn update(&mut self, id: i64, update: UpdateTodo) -> Result<Option<Todo>> {
+        let mut conn = self.pool.acquire().await?;
+        let todo = self.update_with(&mut conn, id, update).await?;
+        self.notify(TodoEventKind::Updated, todo.as_ref().map(|todo| todo.id));
+        Ok(todo)
+    }
+    /// [`TodoRepository::update`] on `conn`, which may be inside a transaction, without events
+    async fn update_with(
+        &self,
+        conn: &mut SqliteConnection,
+        id: i64,
+        update: UpdateTodo,
+    ) -> Result<Option<Todo>> {
+        let todo: Option<Todo> = timed(
+            sqlx::query_as(&format!(
+                "select * from {table} where id = ?1 limit 1",
+                table = self.table
+            ))
+            .bind(id)
+            .fetch_optional(&mut *conn),
+        )
+        .await?;
+        let todo = todo.ok_or(RepositoryError::NotFound(id))?;
+        let title = update
+            .title
+            .map(|title| normalize_title(&title))
+            .unwrap_or_else(|| todo.title.clone());
+        let notes = update.notes.unwrap_or_else(|| todo.notes.clone());
+        let completed = update.completed.unwrap_or(todo.completed);
+        let priority = update.priority.unwrap_or(todo.priority);
+        if title == todo.title
+            && notes == todo.notes
+            && completed == todo.completed
+            && priority == todo.priority
+        {
+            return Ok(None);
+        }
+        let query = format!(
+            r#"UPDATE {table}
+            SET title = ?2, notes = ?3, completed = ?4, priority = ?5, updated_at = CURRENT_TIMESTAMP,
+                completed_at = CASE WHEN ?4 THEN COALESCE(completed_at, CURRENT_TIMESTAMP) END
+            where id = ?1
+            RETURNING *"#,
+            table = self.table
+        );
+        let todo: Option<Todo> = timed(
+            sqlx::query_as(&query)
+                .bind(id)
+                .bind(title)
+                .bind(notes)
+                .bind(completed)
+                .bind(priority)
+                .fetch_optional(&mut *conn),
+        )
+        .await?;
+        Ok(todo)
+    }
+    /// Apply `operations` in order in one transaction
+    ///
+    /// The first failing operation rolls everything back and its error, naming the
+    /// operation, is returned. With `continue_on_error` only the failing operations
+    /// are undone, they get a [`BatchOutcome::Error`] and the rest is committed.
+    #[tracing::instrument(skip(self, operations), fields(count = operations.len()))]
+    pub async fn batch(
+        &mut self,
+        operations: Vec<BatchOperation>,
+        continue_on_error: bool,
+    ) -> Result<Vec<BatchOutcome>> {
+        let mut tx = self.pool.begin().await?;
+        let mut outcomes = Vec::with_capacity(operations.len());
+        let mut events = Vec::new();
+        for (index, operation) in operations.into_iter().enumerate() {
+            if continue_on_error {
+                sqlx::query("SAVEPOINT batch_operation")
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            match self.apply(&mut tx, operation).await {
+                Ok((outcome, event)) => {
+                    if continue_on_error {
+                        sqlx::query("RELEASE batch_operation")
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                    outcomes.push(outcome);
+                    events.extend(event);
+                }
+                Err(err) if continue_on_error => {
+                    sqlx::query("ROLLBACK TO batch_operation")
+                        .execute(&mut *tx)
+                        .await?;
+                    sqlx::query("RELEASE batch_operation")
+                        .execute(&mut *tx)
+                        .await?;
+                    outcomes.push(BatchOutcome::Error(err.to_string()));
+                }
+                Err(err) => {
+                    let context = format!("operation {index} failed, nothing was applied");
+                    return Err(match err {
+                        RepositoryError::NotFound(id) => {
+                            RepositoryError::Validation(format!("{context}: todo {id} not found"))
+                        }
+                        RepositoryError::Validation(message) => {
+                            RepositoryError::Validation(format!("{context}: {message}"))
+                        }
+                        RepositoryError::Conflict(message) => {
+                            RepositoryError::Conflict(format!("{context}: {message}"))
+                        }
+                        err => err,
+                    });
+                }
+            }
+        }
+        tx.commit().await?;
+        for (kind, id) in events {
+            self.notify(kind, [id]);
+        }
+        Ok(outcomes)
+    }
+    /// Run one batch operation on `conn`, returning the event to send after commit
+    async fn apply(
+        &self,
+        conn: &mut SqliteConnection,
+        operation: BatchOperation,
+    ) -> Result<(BatchOutcome, Option<(TodoEventKind, i64)>)> {
+        match operation {
+            BatchOperation::Create(todo) => {
+                todo.validate()
+                    .map_err(|err| RepositoryError::Validation(err.to_string()))?;
+                let created: Todo = timed(
+                    sqlx::query_as(&format!(
+                        "INSERT INTO {table} ( title, notes, completed, priority, completed_at, due_date ) VALUES ( ?1, ?2, ?3, ?4, CASE WHEN ?3 THEN CURRENT_TIMESTAMP END, ?5 ) RETURNING *",
+                        table = self.table
+                    ))
+                    .bind(normalize_title(&todo.title))
+                    .bind(todo.notes)
+                    .bind(todo.completed)
+                    .bind(todo.priority.unwrap_or_default())
+                    .bind(todo.due_date)
+                    .fetch_one(&mut *conn),
+                )
+                .await?;
+                let event = (TodoEventKind::Created, created.id);
+                Ok((BatchOutcome::Created(created), Some(event)))
+            }
+            BatchOperation::Update(BatchUpdate { id, update }) => {
+                update
+                    .validate()
+                    .map_err(|err| RepositoryError::Validation(err.to_string()))?;
+                match self.update_with(conn, id, update).await? {
+                    Some(todo) => Ok((
+                        BatchOutcome::Updated(todo),
+                        Some((TodoEventKind::Updated, id)),
+                    )),
+                    None => {
+                        let todo = timed(
+                            sqlx::query_as(&format!(
+                                "select * from {table} where id = ?1 limit 1",
+                                table = self.table
+                            ))
+                            .bind(id)
+                            .fetch_one(&mut *conn),
+                        )
+                        .await?;
+                        Ok((BatchOutcome::Updated(todo), None))
+                    }
+                }
+            }
+            BatchOperation::Delete(id) => {
+                let rows_affected = timed(
+                    sqlx::query(&format!(
+                        "DELETE from {table} where id = ?1",
+                        table = self.table
+                    ))
+                    .bind(id)
+                    .execute(&mut *conn),
+                )
+                .await?
+                .rows_affected();
+                if rows_affected == 0 {
+                    return Err(RepositoryError::NotFound(id));
+                }
+                Ok((
+                    BatchOutcome::Deleted(id),
+                    Some((TodoEventKind::Deleted, id)),
+                ))
+            }
+        }
+    }
+    /// Insert `todo` with its id, or overwrite the todo already stored under that id
+    ///
+    /// On conflict the stored `created_at` is kept and `updated_at` is bumped.
+    #[tracing::instrument(skip(self, todo), fields(id = todo.id))]
+    pub async fn upsert(&mut self, todo: Todo) -> Result<()> {
+        let query = format!(
+            r#"INSERT INTO {table} (id, title, notes, completed, assigned, priority, created_at, updated_at, parent_id, position, completed_at, due_date)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+            ON CONFLICT(id) DO UPDATE SET
+                title = excluded.title,
+                notes = excluded.notes,
+                completed = excluded.completed,
+                assigned = excluded.assigned,
+                priority = excluded.priority,
+                parent_id = excluded.parent_id,
+                position = excluded.position,
+                completed_at = excluded.completed_at,
+                due_date = excluded.due_date,
+                updated_at = CURRENT_TIMESTAMP"#,
+            table = self.table
+        );
+        timed(
+            sqlx::query(&query)
+                .bind(todo.id)
+                .bind(todo.title)
+                .bind(todo.notes)
+                .bind(todo.completed)
+                .bind(todo.assigned)
+                .bind(todo.priority)
+                .bind(todo.created_at)
+                .bind(todo.updated_at)
+                .bind(todo.parent_id)
+                .bind(todo.position)
+                .bind(todo.completed_at)
+                .bind(todo.due_date)
+                .execute(&self.pool),
+        )
+        .await?;
+        self.notify(TodoEventKind::Updated, [todo.id]);
+        Ok(())
+    }
+    /// Assign todo to a person, empty string means unassigned
+    #[tracing::instrument(skip(self))]
+    pub async fn assign(&mut self, id: i64, person: &str) -> Result<Todo> {
+        let rows_affected = timed(
+            sqlx::query(&format!(
+                "UPDATE {table} SET assigned = ?2, updated_at = CURRENT_TIMESTAMP where id = ?1",
+                table = self.table
+            ))
+            .bind(id)
+            .bind(person)
+            .execute(&self.pool),
+        )
+        .await?
+        .rows_affected();
+        if rows_affected == 0 {
+            return Err(RepositoryError::NotFound(id));
+        }
+        self.notify(TodoEventKind::Updated, [id]);
+        self.get(id).await
+    }
+    /// Everyone at least one todo is assigned to, sorted and without duplicates
+    #[tracing::instrument(skip(self))]
+    pub async fn distinct_assignees(&mut self) -> Result<Vec<String>> {
+        let assignees = timed(
+            sqlx::query_scalar(&format!(
+                "SELECT DISTINCT assigned FROM {table} WHERE assigned <> '' ORDER BY assigned",
+                table = self.table
+            ))
+            .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(assignees)
+    }
+    /// Bump `updated_at` without changing anything else, returning rows affected
+    #[tracing::instrument(skip(self))]
+    pub async fn touch(&mut self, id: i64) -> Result<u64> {
+        let rows_affected = timed(
+            sqlx::query(&format!(
+                "UPDATE {table} SET updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                table = self.table
+            ))
+            .bind(id)
+            .execute(&self.pool),
+        )
+        .await?
+        .rows_affected();
+        if rows_affected > 0 {
+            self.notify(TodoEventKind::Updated, [id]);
+        }
+        Ok(rows_affected)
+    }
+    /// Replace notes of a todo, leaving the other fields untouched
+    #[tracing::instrument(skip(self, notes))]
+    pub async fn set_notes(&mut self, id: i64, notes: &str) -> Result<Todo> {
+        let rows_affected = timed(
+            sqlx::query(&format!(
+                "UPDATE {table} SET notes = ?2, updated_at = CURRENT_TIMESTAMP where id = ?1",
+                table = self.table
+            ))
+            .bind(id)
+            .bind(notes)
+            .execute(&self.pool),
+        )
+        .await?
+        .rows_affected();
+        if rows_affected == 0 {
+            return Err(RepositoryError::NotFound(id));
+        }
+        self.notify(TodoEventKind::Updated, [id]);
+        self.get(id).await
+    }
+    /// Replace notes of a todo only while they still equal `expected`
+    ///
+    /// Returns `false` when someone else changed the notes in the meantime, so
+    /// their edit is not overwritten.
+    #[tracing::instrument(skip(self, expected, new))]
+    pub async fn compare_and_set_notes(
+        &mut self,
+        id: i64,
+        expected: &str,
+        new: &str,
+    ) -> Result<bool> {
+        let rows_affected = timed(
+            sqlx::query(&format!(
+                "UPDATE {table} SET notes = ?3, updated_at = CURRENT_TIMESTAMP where id = ?1 AND notes = ?2",
+                table = self.table
+            ))
+            .bind(id)
+            .bind(expected)
+            .bind(new)
+            .execute(&self.pool),
+        )
+        .await?
+        .rows_affected();
+        if rows_affected == 0 {
+            // tell a missing todo apart from a lost race
+            self.get(id).await?;
+            return Ok(false);
+        }
+        self.notify(TodoEventKind::Updated, [id]);
+        Ok(true)
+    }
+    /// Complete every open todo whose title contains `query`, returning how many changed
+    ///
+    /// Wildcards in `query` are matched literally.
+    #[tracing::instrument(skip(self))]
+    pub async fn complete_matching(&mut self, query: &str) -> Result<u64> {
+        let sql = format!(
+            r#"UPDATE {table} SET completed = 1, updated_at = CURRENT_TIMESTAMP, completed_at = CURRENT_TIMESTAMP
+            WHERE completed = 0 AND title LIKE '%' || ?1 || '%' ESCAPE '\'
+            RETURNING id"#,
+            table = self.table
+        );
+        let ids: Vec<i64> = timed(
+            sqlx::query_scalar(&sql)
+                .bind(escape_like(query))
+                .fetch_all(&self.pool),
+        )
+        .await?;
+        self.notify(TodoEventKind::Updated, ids.iter().copied());
+        Ok(ids.len() as u64)
+    }
+    /// Reopen every completed todo and clear `completed_at`, returning how many changed
+    #[tracing::instrument(skip(self))]
+    pub async fn reopen_all(&mut self) -> Result<u64> {
+        let sql = format!(
+            "UPDATE {table} SET completed = 0, updated_at = CURRENT_TIMESTAMP, completed_at = NULL WHERE completed = 1 RETURNING id",
+            table = self.table
+        );
+        let ids: Vec<i64> = timed(sqlx::query_scalar(&sql).fetch_all(&self.pool)).await?;
+        self.notify(TodoEventKind::Updated, ids.iter().copied());
+        Ok(ids.len() as u64)
+    }
+    /// Move todo `id` under `parent` and/or to `position` in one transaction
+    ///
+    /// `parent: Some(None)` makes it a top level todo, `None` keeps the current parent.
+    /// A todo can't become a subtask of itself or of one of its subtasks.
+    #[tracing::instrument(skip(self))]
+    pub async fn move_todo(
+        &mut self,
+        id: i64,
+        parent: Option<Option<i64>>,
+        position: Option<i64>,
+    ) -> Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        let select = format!("SELECT * FROM {table} WHERE id = ?1", table = self.table);
+        let todo: Option<Todo> =
+            timed(sqlx::query_as(&select).bind(id).fetch_optional(&mut *tx)).await?;
+        let todo = todo.ok_or(RepositoryError::NotFound(id))?;
+        if let Some(Some(parent_id)) = parent {
+            let exists: Option<Todo> = timed(
+                sqlx::query_as(&select)
+                    .bind(parent_id)
+                    .fetch_optional(&mut *tx),
+            )
+            .await?;
+            exists.ok_or(RepositoryError::NotFound(parent_id))?;
+            // finding `id` above the new parent means a cycle
+            if self.is_self_or_ancestor(&mut tx, id, parent_id).await? {
+                return Err(RepositoryError::Validation(format!(
+                    "todo {id} can't become a subtask of {parent_id}, that would create a cycle"
+                )));
+            }
+        }
+        let query = format!(
+            r#"UPDATE {table} SET parent_id = ?2, position = ?3, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?1
+            RETURNING *"#,
+            table = self.table
+        );
+        let moved: Todo = timed(
+            sqlx::query_as(&query)
+                .bind(id)
+                .bind(parent.unwrap_or(todo.parent_id))
+                .bind(position.unwrap_or(todo.position))
+                .fetch_one(&mut *tx),
+        )
+        .await?;
+        tx.commit().await?;
+        self.notify(TodoEventKind::Updated, [id]);
+        Ok(moved)
+    }
+    /// Exchange the positions of todos `a_id` and `b_id` in one transaction
+    #[tracing::instrument(skip(self))]
+    pub async fn swap_positions(&mut self, a_id: i64, b_id: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let select = format!(
+            "SELECT position FROM {table} WHERE id = ?1",
+            table = self.table
+        );
+        let mut positions = [0; 2];
+        for (position, id) in positions.iter_mut().zip([a_id, b_id]) {
+            let found: Option<i64> = timed(
+                sqlx::query_scalar(&select)
+                    .bind(id)
+                    .fetch_optional(&mut *tx),
+            )
+            .await?;
+            *position = found.ok_or(RepositoryError::NotFound(id))?;
+        }
+        let update = format!(
+            "UPDATE {table} SET position = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+            table = self.table
+        );
+        for (id, position) in [(a_id, positions[1]), (b_id, positions[0])] {
+            timed(
+                sqlx::query(&update)
+                    .bind(id)
+                    .bind(position)
+                    .execute(&mut *tx),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        self.notify(TodoEventKind::Updated, [a_id, b_id]);
+        Ok(())
+    }
+    /// Give every todo the position of its id in `ids`, in one transaction
+    ///
+    /// `ids` must list each todo exactly once, otherwise nothing changes and a
+    /// [`RepositoryError::Validation`] names the missing, unknown or repeated ids.
+    #[tracing::instrument(skip(self, ids), fields(count = ids.len()))]
+    pub async fn set_order(&mut self, ids: &[i64]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let existing: BTreeSet<i64> = timed(
+            sqlx::query_scalar(&format!("SELECT id FROM {table}", table = self.table))
+                .fetch_all(&mut *tx),
+        )
+        .await?
+        .into_iter()
+        .collect();
+        let mut seen = BTreeSet::new();
+        let repeated: Vec<i64> = ids.iter().copied().filter(|id| !seen.insert(*id)).collect();
+        let unknown: Vec<i64> = seen.difference(&existing).copied().collect();
+        let missing: Vec<i64> = existing.difference(&seen).copied().collect();
+        let mut problems = Vec::new();
+        for (what, ids) in [
+            ("missing", missing),
+            ("unknown", unknown),
+            ("repeated", repeated),
+        ] {
+            if !ids.is_empty() {
+                let ids: Vec<String> = ids.iter().map(i64::to_string).collect();
+                problems.push(format!("{what} {}", ids.join(",")));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(RepositoryError::Validation(format!(
+                "order must list every todo once: {}",
+                problems.join("; ")
+            )));
+        }
+        let update = format!(
+            "UPDATE {table} SET position = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?1 AND position != ?2",
+            table = self.table
+        );
+        let mut changed = Vec::new();
+        for (position, id) in ids.iter().enumerate() {
+            let result = timed(
+                sqlx::query(&update)
+                    .bind(id)
+                    .bind(position as i64)
+                    .execute(&mut *tx),
+            )
+            .await?;
+            if result.rows_affected() > 0 {
+                changed.push(*id);
+            }
+        }
+        tx.commit().await?;
+        self.notify(TodoEventKind::Updated, changed);
+        Ok(())
+    }
+    /// Whether `ancestor` is `id` itself or one of its parents, walking up from `id`
+    async fn is_self_or_ancestor(
+        &self,
+        conn: &mut SqliteConnection,
+        ancestor: i64,
+        id: i64,
+    ) -> Result<bool> {
+        let query = format!(
+            r#"WITH RECURSIVE ancestors(id) AS (
+                SELECT ?1
+                UNION
+                SELECT {table}.parent_id FROM {table} JOIN ancestors ON {table}.id = ancestors.id
+                WHERE {table}.parent_id IS NOT NULL
+            )
+            SELECT EXISTS(SELECT 1 FROM ancestors WHERE id = ?2)"#,
+            table = self.table
+        );
+        let found: bool = timed(
+            sqlx::query_scalar(&query)
+                .bind(id)
+                .bind(ancestor)
+                .fetch_one(&mut *conn),
+        )
+        .await?;
+        Ok(found)
+    }
+    /// Merge duplicate todo `merge_id` into `keep_id`
+    ///
+    /// Notes of the merged todo are appended to the kept one, its tags and subtasks
+    /// move over and the merged row is deleted, all in one transaction. Returns the
+    /// resulting kept todo.
+    #[tracing::instrument(skip(self))]
+    pub async fn merge(&mut self, keep_id: i64, merge_id: i64) -> Result<Todo> {
+        if keep_id == merge_id {
+            return Err(RepositoryError::Validation(format!(
+                "cannot merge todo {keep_id} into itself"
+            )));
+        }
+        let mut tx = self.pool.begin().await?;
+        let keep: Option<Todo> = timed(
+            sqlx::query_as(&format!(
+                "select * from {table} where id = ?1",
+                table = self.table
+            ))
+            .bind(keep_id)
+            .fetch_optional(&mut *tx),
+        )
+        .await?;
+        let keep = keep.ok_or(RepositoryError::NotFound(keep_id))?;
+        let merged: Option<Todo> = timed(
+            sqlx::query_as(&format!(
+                "select * from {table} where id = ?1",
+                table = self.table
+            ))
+            .bind(merge_id)
+            .fetch_optional(&mut *tx),
+        )
+        .await?;
+        let merged = merged.ok_or(RepositoryError::NotFound(merge_id))?;
+        // a direct subtask takes the merged todo's place, anything deeper would
+        // end up as a subtask of its own descendant
+        if keep.parent_id != Some(merge_id)
+            && self.is_self_or_ancestor(&mut tx, merge_id, keep_id).await?
+        {
+            return Err(RepositoryError::Validation(format!(
+                "cannot merge todo {merge_id} into {keep_id}, a nested subtask of it"
+            )));
+        }
+        let notes = match (keep.notes.is_empty(), merged.notes.is_empty()) {
+            (_, true) => keep.notes,
+            (true, false) => merged.notes,
+            (false, false) => format!("{}\n{}", keep.notes, merged.notes),
+        };
+        timed(
+            sqlx::query(&format!(
+                "UPDATE {table} SET notes = ?2, updated_at = CURRENT_TIMESTAMP where id = ?1",
+                table = self.table
+            ))
+            .bind(keep_id)
+            .bind(notes)
+            .execute(&mut *tx),
+        )
+        .await?;
+        timed(
+            sqlx::query(&format!(
+                "INSERT OR IGNORE INTO {table}_tags (todo_id, tag) SELECT ?1, tag FROM {table}_tags where todo_id = ?2",
+                table = self.table
+            ))
+            .bind(keep_id)
+            .bind(merge_id)
+            .execute(&mut *tx),
+        )
+        .await?;
+        // subtasks of the merged todo move over instead of being deleted with it
+        timed(
+            sqlx::query(&format!(
+                "UPDATE {table} SET parent_id = CASE WHEN id = ?1 THEN ?3 ELSE ?1 END where parent_id = ?2",
+                table = self.table
+            ))
+            .bind(keep_id)
+            .bind(merge_id)
+            .bind(merged.parent_id)
+            .execute(&mut *tx),
+        )
+        .await?;
+        timed(
+            sqlx::query(&format!(
+                "DELETE from {table} where id = ?1",
+                table = self.table
+            ))
+            .bind(merge_id)
+            .execute(&mut *tx),
+        )
+        .await?;
+        let todo: Todo = timed(
+            sqlx::query_as(&format!(
+                "select * from {table} where id = ?1",
+                table = self.table
+            ))
+            .bind(keep_id)
+            .fetch_one(&mut *tx),
+        )
+        .await?;
+        tx.commit().await?;
+        self.notify(TodoEventKind::Updated, [keep_id]);
+        self.notify(TodoEventKind::Deleted, [merge_id]);
+        Ok(todo)
+    }
+    /// Delete todo id
+    #[tracing::instrument(skip(self))]
+    pub async fn delete(&mut self, id: i64) -> Result<u64> {
+        let rows_affected = timed(
+            sqlx::query(&format!(
+                "DELETE from {table} where id = ?1",
+                table = self.table
+            ))
+            .bind(id)
+            .execute(&self.pool),
+        )
+        .await?
+        .rows_affected();
+        if rows_affected > 0 {
+            self.notify(TodoEventKind::Deleted, [id]);
+        }
+        Ok(rows_affected)
+    }
+    /// Delete all completed todos, open ones stay
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_completed(&mut self) -> Result<u64> {
+        let ids: Vec<i64> = timed(
+            sqlx::query_scalar(&format!(
+                "DELETE from {table} where completed = 1 RETURNING id",
+                table = self.table
+            ))
+            .fetch_all(&self.pool),
+        )
+        .await?;
+        self.notify(TodoEventKind::Deleted, ids.iter().copied());
+        Ok(ids.len() as u64)
+    }
+    /// Up to `limit` completed todos, most recently completed first
+    ///
+    /// Todos completed before `completed_at` was tracked come last, newest id first.
+    #[tracing::instrument(skip(self))]
+    pub async fn latest_completed(&mut self, limit: u32) -> Result<Vec<Todo>> {
+        let todos: Vec<Todo> = timed(
+            sqlx::query_as(&format!(
+                "SELECT * FROM {table} WHERE completed = 1 ORDER BY completed_at IS NULL, completed_at DESC, id DESC LIMIT ?1",
+                table = self.table
+            ))
+            .bind(limit)
+            .fetch_all(&self.pool),
+        )
+        .await?;
+        Ok(todos)
+    }
+    /// Delete todos completed before `cutoff`, returning how many were deleted
+    ///
+    /// Open todos and todos without a completion time are kept.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_completed_before(&mut self, cutoff: NaiveDateTime) -> Result<u64> {
+        let ids: Vec<i64> = timed(
+            sqlx::query_scalar(&format!(
+                "DELETE from {table} where completed = 1 AND completed_at < ?1 RETURNING id",
+                table = self.table
+            ))
+            .bind(cutoff)
+            .fetch_all(&self.pool),
+        )
+        .await?;
+        self.notify(TodoEventKind::Deleted, ids.iter().copied());
+        Ok(ids.len() as u64)
+    }
+    /// Cleanup todos table
+    #[tracing::instrument(skip(self))]
+    pub async fn cleanup(&mut self) -> Result<u64> {
+        let ids: Vec<i64> = timed(
+            sqlx::query_scalar(&format!(
+                "DELETE from {table} RETURNING id",
+                table = self.table
+            ))
+            .fetch_all(&self.pool),
+        )
+        .await?;
+        self.notify(TodoEventKind::Deleted, ids.iter().copied());
+        Ok(ids.len() as u64)
+    }
+    /// Delete all todos so the next one gets id 1 again, without sending events
+    ///
+    /// No table uses `AUTOINCREMENT`, so SQLite hands out the next rowid after the
+    /// largest one left and an empty table starts over at 1.
+    #[cfg(any(test, feature = "testing"))]
+    #[tracing::instrument(skip(self))]
+    pub async fn reset(&mut self) -> Result<()> {
+        timed(sqlx::query(&format!("DELETE from {table}", table = self.table)).execute(&self.pool))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use tracing::Instrument;
+
+    async fn create_repo_and_table() -> anyhow::Result<TodoRepository> {
+        TodoRepository::in_memory().await
+    }
+
+    async fn create_todo(repo: &mut TodoRepository, text: &str) -> Result<i64> {
+        let create_todo = CreateTodo {
+            title: text.to_owned(),
+            ..Default::default()
+        };
+        let id = repo.create(create_todo).await?;
+        Ok(id)
+    }
+
+    #[tokio::test]
+    async fn test_create() {
+        let mut repo = create_repo_and_table().await.unwrap();
+
+        let first_id = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let second_id = create_todo(&mut repo, "Test todo 2").await.unwrap();
+        let third_id = create_todo(&mut repo, "Test todo 3").await.unwrap();
+        assert_eq!(first_id, 1);
+        assert_eq!(second_id, 2);
+        assert_eq!(third_id, 3);
+    }
+
+    #[tokio::test]
+    async fn test_create_many() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        assert_eq!(repo.count().await.unwrap(), 0);
+        let todos = [
+            CreateTodo {
+                title: "Test todo 1".to_owned(),
+                ..Default::default()
+            },
+            CreateTodo {
+                title: "Test todo 2".to_owned(),
+                priority: Some(Priority::High),
+                ..Default::default()
+            },
+        ];
+        let ids = repo.create_many(&todos).await.unwrap();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(repo.count().await.unwrap(), 2);
+        assert_eq!(repo.get(2).await.unwrap().priority, Priority::High);
+    }
+
+    #[tokio::test]
+    async fn test_get() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 2").await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 3").await.unwrap();
+
+        let todo1 = repo.get(1).await.unwrap();
+        let todo2 = repo.get(2).await.unwrap();
+        let todo3 = repo.get(3).await.unwrap();
+
+        assert_eq!(todo1.id, 1);
+        assert_eq!(todo2.id, 2);
+        assert_eq!(todo3.id, 3);
+        assert_eq!(todo1.title, String::from("Test todo 1"));
+        assert_eq!(todo2.title, String::from("Test todo 2"));
+        assert_eq!(todo3.title, String::from("Test todo 3"));
+    }
+
+    #[tokio::test]
+    async fn test_get_many() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        let todos = repo.get_many(&[3, 1, 42]).await.unwrap();
+        let ids: Vec<i64> = todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert!(repo.get_many(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_fields() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let id = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let todo = repo.get(id).await.unwrap();
+        let rows = repo
+            .list_fields(Pagination::default(), SortBy::Id, &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::Value::Object(rows[0].clone()),
+            serde_json::to_value(&todo).unwrap()
+        );
+        let rows = repo
+            .list_fields(
+                Pagination::default(),
+                SortBy::Id,
+                &[TodoField::Priority, TodoField::DueDate],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::Value::Object(rows[0].clone()),
+            serde_json::json!({ "priority": "normal", "due_date": null })
+        );
+        assert!(TodoField::parse_list("id,nope")
+            .unwrap_err()
+            .contains("unknown field `nope`"));
+    }
+
+    #[tokio::test]
+    async fn test_get_map() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        let todos = repo.get_map(&[3, 1, 42]).await.unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[&1].title, "Test todo 1");
+        assert_eq!(todos[&3].title, "Test todo 3");
+        assert!(!todos.contains_key(&42));
+        assert!(repo.get_map(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 2").await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 3").await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 4").await.unwrap();
+        let update_text = UpdateTodo {
+            title: Some("Update text only".to_owned()),
+            notes: None,
+            completed: None,
+            priority: None,
+        };
+
+        let update_done = UpdateTodo {
+            title: None,
+            notes: None,
+            completed: Some(true),
+            priority: None,
+        };
+        let update_both = UpdateTodo {
+            title: Some("Update text and complete status".to_owned()),
+            notes: None,
+            completed: Some(true),
+            priority: None,
+        };
+        let update_all = UpdateTodo {
+            title: Some("Update title , note and complete status".to_owned()),
+            notes: Some("Some new notes".to_owned()),
+            completed: Some(true),
+            priority: None,
+        };
+
+        let update_second_id = repo.update(2, update_text).await.unwrap();
+        let update_third_id = repo.update(3, update_done).await.unwrap();
+        let update_fourth_id = repo.update(4, update_both).await.unwrap();
+        let _ = repo.update(1, update_all).await.unwrap();
+
+        assert_eq!(update_second_id.unwrap().title, "Update text only");
+        assert!(update_third_id.unwrap().completed);
+        assert_eq!(update_fourth_id.unwrap().id, 4);
+
+        let todo1 = repo.get(1).await.unwrap();
+        let todo2 = repo.get(2).await.unwrap();
+        let todo3 = repo.get(3).await.unwrap();
+        let todo4 = repo.get(4).await.unwrap();
+        println!("{todo1:?}");
+        println!("{todo2:?}");
+        println!("{todo3:?}");
+        println!("{todo4:?}");
+        assert_eq!(todo1.id, 1);
+        assert_eq!(todo2.id, 2);
+        assert_eq!(todo3.id, 3);
+        assert_eq!(
+            todo1.title,
+            String::from("Update title , note and complete status")
+        );
+        assert_eq!(todo2.title, String::from("Update text only"));
+        assert_eq!(todo3.title, String::from("Test todo 3"));
+        assert_eq!(todo4.title, String::from("Update text and complete status"));
+        assert_eq!(todo1.notes, String::from("Some new notes"));
+        assert!(todo1.completed);
+        assert!(!todo2.completed);
+        assert!(todo3.completed);
+        assert!(todo4.completed);
+    }
+
+    #[tokio::test]
+    async fn test_update_noop() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let same = UpdateTodo {
+            title: Some("Test todo 1".to_owned()),
+            completed: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(repo.update(1, same).await.unwrap(), None);
+        assert_eq!(repo.update(1, UpdateTodo::default()).await.unwrap(), None);
+        let changed = UpdateTodo {
+            completed: Some(true),
+            ..Default::default()
+        };
+        assert!(repo.update(1, changed).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_update_returns_row() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let update = UpdateTodo {
+            notes: Some("Some new notes".to_owned()),
+            priority: Some(Priority::Urgent),
+            ..Default::default()
+        };
+        let todo = repo.update(1, update).await.unwrap().unwrap();
+        assert_eq!(todo.notes, "Some new notes");
+        assert_eq!(todo.priority, Priority::Urgent);
+        assert_eq!(todo, repo.get(1).await.unwrap());
+        assert!(matches!(
+            repo.update(42, UpdateTodo::default()).await,
+            Err(RepositoryError::NotFound(42))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_list() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 2").await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 3").await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 4").await.unwrap();
+        let pagination = Pagination {
+            offset: None,
+            limit: None,
+        };
+        let todos = repo.list(pagination).await.unwrap();
+        println!("{todos:?}");
+        assert_eq!(todos.len(), 4);
+        // test offset only
+        let todos = repo.list(Pagination::new(Some(1), None)).await.unwrap();
+        println!("{todos:?}");
+        assert_eq!(todos.len(), 3);
+        assert_eq!(todos.first().unwrap().id, 2);
+        // test limit  only
+        let todos = repo.list(Pagination::new(None, Some(3))).await.unwrap();
+        println!("{todos:?}");
+        assert_eq!(todos.len(), 3);
+        assert_eq!(todos.first().unwrap().id, 1);
+        // test offset and limit
+        let todos = repo.list(Pagination::new(Some(1), Some(2))).await.unwrap();
+        println!("{todos:?}");
+        assert_eq!(todos.len(), 2);
+        // limit 0 means no rows
+        let todos = repo.list(Pagination::new(None, Some(0))).await.unwrap();
+        assert!(todos.is_empty());
+        // offset past the end is an empty page, not an error
+        let todos = repo.list(Pagination::new(Some(10), None)).await.unwrap();
+        assert!(todos.is_empty());
+        let todos = repo.list(Pagination::new(Some(4), Some(2))).await.unwrap();
+        assert!(todos.is_empty());
+    }
+    #[tokio::test]
+    async fn test_delete() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 2").await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 3").await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 4").await.unwrap();
+
+        let affected_rows = repo.delete(3).await.unwrap();
+        assert_eq!(affected_rows, 1);
+        let todos = repo
+            .list(Pagination {
+                offset: None,
+                limit: None,
+            })
+            .await
+            .unwrap();
+        println!("{todos:?}");
+        assert_eq!(todos.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_assign() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+
+        let todo = repo.get(1).await.unwrap();
+        assert_eq!(todo.assigned, String::new());
+        let todo = repo.assign(1, "alice").await.unwrap();
+        assert_eq!(todo.assigned, String::from("alice"));
+        let todo = repo.assign(1, "bob").await.unwrap();
+        assert_eq!(todo.assigned, String::from("bob"));
+        let todo = repo.assign(1, "").await.unwrap();
+        assert_eq!(todo.assigned, String::new());
+        assert!(repo.assign(42, "alice").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_sorted() {
+        let seed = [
+            CreateTodo {
+                title: "b low".to_owned(),
+                priority: Some(Priority::Low),
+                ..Default::default()
+            },
+            CreateTodo {
+                title: "a normal".to_owned(),
+                priority: None,
+                ..Default::default()
+            },
+            CreateTodo {
+                title: "c urgent".to_owned(),
+                priority: Some(Priority::Urgent),
+                ..Default::default()
+            },
+        ];
+        let mut repo = TodoRepository::in_memory_with(&seed).await.unwrap();
+        // default list surfaces urgent todos first
+        let todos = repo.list(Pagination::default()).await.unwrap();
+        let ids: Vec<i64> = todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+        assert_eq!(todos[1].priority, Priority::Normal);
+        let todos = repo
+            .list_sorted(Pagination::default(), SortBy::Id)
+            .await
+            .unwrap();
+        let ids: Vec<i64> = todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        let todos = repo
+            .list_sorted(Pagination::default(), SortBy::Title)
+            .await
+            .unwrap();
+        let ids: Vec<i64> = todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![2, 1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_list_sorted_by_title_ignores_case() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Zebra", "apple", "Banana", "cherry"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        let todos = repo
+            .list_sorted(Pagination::default(), SortBy::Title)
+            .await
+            .unwrap();
+        let titles: Vec<&str> = todos.iter().map(|todo| todo.title.as_str()).collect();
+        assert_eq!(titles, vec!["apple", "Banana", "cherry", "Zebra"]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_completed() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        for id in [1, 3] {
+            let done = UpdateTodo {
+                completed: Some(true),
+                ..Default::default()
+            };
+            repo.update(id, done).await.unwrap();
+        }
+        assert_eq!(repo.delete_completed().await.unwrap(), 2);
+        let todos = repo.list(Pagination::default()).await.unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].id, 2);
+        assert_eq!(repo.delete_completed().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_completed_at() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        assert_eq!(repo.get(1).await.unwrap().completed_at, None);
+        let done = UpdateTodo {
+            completed: Some(true),
+            ..Default::default()
+        };
+        let todo = repo.update(1, done).await.unwrap().unwrap();
+        let completed_at = todo.completed_at.unwrap();
+        // editing a completed todo keeps its completion time
+        let rename = UpdateTodo {
+            title: Some("Renamed".to_owned()),
+            ..Default::default()
+        };
+        let todo = repo.update(1, rename).await.unwrap().unwrap();
+        assert_eq!(todo.completed_at, Some(completed_at));
+        repo.reopen_all().await.unwrap();
+        assert_eq!(repo.get(1).await.unwrap().completed_at, None);
+        let id = repo
+            .create(CreateTodo {
+                title: "Test todo 2".to_owned(),
+                completed: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(repo.get(id).await.unwrap().completed_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_delete_completed_before() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for (id, completed_at) in [
+            (1, Some("2024-01-01 10:00:00")),
+            (2, Some("2024-03-01 10:00:00")),
+            (3, None),
+            (4, Some("2024-01-15 10:00:00")),
+        ] {
+            let todo = Todo {
+                completed: completed_at.is_some(),
+                completed_at: completed_at.map(datetime),
+                ..Todo::fixture(id, datetime("2024-01-01 09:00:00"))
+            };
+            repo.upsert(todo).await.unwrap();
+        }
+        let cutoff = datetime("2024-02-01 00:00:00");
+        assert_eq!(repo.delete_completed_before(cutoff).await.unwrap(), 2);
+        let todos = repo
+            .list_sorted(Pagination::default(), SortBy::Id)
+            .await
+            .unwrap();
+        let ids: Vec<i64> = todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+        assert_eq!(repo.delete_completed_before(cutoff).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_latest_completed() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for (id, completed_at) in [
+            (1, Some("2024-01-02 10:00:00")),
+            (2, None),
+            (3, Some("2024-01-03 10:00:00")),
+            (4, Some("2024-01-01 10:00:00")),
+            (5, None),
+        ] {
+            let todo = Todo {
+                completed: id != 5,
+                completed_at: completed_at.map(datetime),
+                ..Todo::fixture(id, datetime("2024-01-01 09:00:00"))
+            };
+            repo.upsert(todo).await.unwrap();
+        }
+        let ids = |todos: Vec<Todo>| -> Vec<i64> { todos.iter().map(|todo| todo.id).collect() };
+        assert_eq!(
+            ids(repo.latest_completed(10).await.unwrap()),
+            vec![3, 1, 4, 2]
+        );
+        assert_eq!(ids(repo.latest_completed(2).await.unwrap()), vec![3, 1]);
+        assert!(repo.latest_completed(0).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reset() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        let mut events = repo.subscribe();
+        repo.reset().await.unwrap();
+        assert_eq!(repo.count().await.unwrap(), 0);
+        assert!(events.try_recv().is_err());
+        let id = create_todo(&mut repo, "Test todo 4").await.unwrap();
+        assert_eq!(id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_span() {
+        use std::sync::{Arc, Mutex};
+        use tracing::span::{Attributes, Id};
+        use tracing_subscriber::layer::{Context, SubscriberExt};
+        use tracing_subscriber::util::SubscriberInitExt;
+        use tracing_subscriber::Layer;
+
+        /// Records the names of created spans
+        struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+        impl<S: tracing::Subscriber> Layer<S> for SpanNames {
+            fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+                self.0.lock().unwrap().push(attrs.metadata().name());
+            }
+        }
+
+        let mut repo = create_repo_and_table().await.unwrap();
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let _guard = tracing_subscriber::registry()
+            .with(SpanNames(names.clone()))
+            .set_default();
+        repo.list(Pagination::new(None, Some(3))).await.unwrap();
+        let names = names.lock().unwrap();
+        assert!(names.contains(&"list"));
+        assert!(names.contains(&"list_sorted"));
+    }
+
+    #[tokio::test]
+    async fn test_merge() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Buy milk").await.unwrap();
+        let _ = create_todo(&mut repo, "Buy milk!").await.unwrap();
+        for (id, notes) in [(1, "from the shop"), (2, "two bottles")] {
+            let update = UpdateTodo {
+                notes: Some(notes.to_owned()),
+                ..Default::default()
+            };
+            repo.update(id, update).await.unwrap();
+        }
+        let todo = repo.merge(1, 2).await.unwrap();
+        assert_eq!(todo.id, 1);
+        assert_eq!(todo.title, "Buy milk");
+        assert_eq!(todo.notes, "from the shop\ntwo bottles");
+        assert!(repo.get(2).await.is_err());
+        assert_eq!(repo.count().await.unwrap(), 1);
+        // failed merges leave the table untouched
+        assert!(repo.merge(1, 42).await.is_err());
+        assert!(repo.merge(1, 1).await.is_err());
+        assert_eq!(
+            repo.get(1).await.unwrap().notes,
+            "from the shop\ntwo bottles"
+        );
+    }
+
+    fn datetime(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upsert_insert() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let todo = Todo {
+            title: "Synced todo".to_owned(),
+            notes: "from the phone".to_owned(),
+            completed: true,
+            assigned: "alice".to_owned(),
+            priority: Priority::High,
+            updated_at: datetime("2024-01-02 10:00:00"),
+            ..Todo::fixture(42, datetime("2024-01-01 10:00:00"))
+        };
+        repo.upsert(todo.clone()).await.unwrap();
+        assert_eq!(repo.get(42).await.unwrap(), todo);
+        assert_eq!(repo.count().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_upsert_update() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let id = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        sqlx::query("UPDATE todos SET updated_at = '2000-01-01 00:00:00'")
+            .execute(&repo.pool)
+            .await
+            .unwrap();
+        let stored = repo.get(id).await.unwrap();
+        let incoming = Todo {
+            title: "Synced title".to_owned(),
+            completed: true,
+            created_at: datetime("2024-01-01 10:00:00"),
+            ..stored.clone()
+        };
+        repo.upsert(incoming).await.unwrap();
+        let todo = repo.get(id).await.unwrap();
+        assert_eq!(todo.title, "Synced title");
+        assert!(todo.completed);
+        assert_eq!(todo.created_at, stored.created_at);
+        assert!(todo.updated_at > stored.updated_at);
+        assert_eq!(repo.count().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_display() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Buy milk").await.unwrap();
+        let _ = create_todo(&mut repo, "Walk the dog").await.unwrap();
+        let updated = repo
+            .update(
+                2,
+                UpdateTodo {
+                    completed: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(updated.is_some());
+        assert_eq!(repo.get(1).await.unwrap().to_line(), "[ ] #1 Buy milk");
+        assert_eq!(
+            repo.get(2).await.unwrap().to_string(),
+            "[x] #2 Walk the dog"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_tables() {
+        let repo = create_repo_and_table().await.unwrap();
+        let mut work = repo.clone().with_table("work").unwrap();
+        let mut home = repo.clone().with_table("home_2").unwrap();
+        work.create_table().await.unwrap();
+        home.create_table().await.unwrap();
+        let _ = create_todo(&mut work, "Write report").await.unwrap();
+        let _ = create_todo(&mut work, "Call boss").await.unwrap();
+        let _ = create_todo(&mut home, "Water plants").await.unwrap();
+        assert_eq!(work.count().await.unwrap(), 2);
+        assert_eq!(home.count().await.unwrap(), 1);
+        assert_eq!(repo.clone().count().await.unwrap(), 0);
+        assert_eq!(home.get(1).await.unwrap().title, "Water plants");
+        home.cleanup().await.unwrap();
+        assert_eq!(work.count().await.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_invalid_table_names() {
+        for name in [
+            "",
+            "1todos",
+            "todos;drop",
+            "my-todos",
+            "todos list",
+            "tôdos",
+        ] {
+            assert!(validate_table_name(name).is_err(), "{name}");
+        }
+        for name in ["todos", "_todos", "Todos_2024"] {
+            assert!(validate_table_name(name).is_ok(), "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_notes() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let todo = repo.set_notes(1, "Some new notes").await.unwrap();
+        assert_eq!(todo.notes, "Some new notes");
+        assert_eq!(todo.title, "Test todo 1");
+        assert!(matches!(
+            repo.set_notes(42, "Some new notes").await,
+            Err(RepositoryError::NotFound(42))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_compare_and_set_notes() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        repo.set_notes(1, "first").await.unwrap();
+        assert!(repo
+            .compare_and_set_notes(1, "first", "second")
+            .await
+            .unwrap());
+        assert_eq!(repo.get(1).await.unwrap().notes, "second");
+        // a stale expectation leaves the other edit alone
+        assert!(!repo
+            .compare_and_set_notes(1, "first", "third")
+            .await
+            .unwrap());
+        assert_eq!(repo.get(1).await.unwrap().notes, "second");
+        assert!(matches!(
+            repo.compare_and_set_notes(42, "", "notes").await,
+            Err(RepositoryError::NotFound(42))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_complete_matching() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in [
+            "Buy groceries",
+            "Groceries list",
+            "Walk the dog",
+            "100% done_ish",
+        ] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        assert_eq!(repo.complete_matching("groceries").await.unwrap(), 2);
+        for (id, completed) in [(1, true), (2, true), (3, false), (4, false)] {
+            assert_eq!(repo.get(id).await.unwrap().completed, completed);
+        }
+        assert_eq!(repo.complete_matching("groceries").await.unwrap(), 0);
+        assert_eq!(repo.complete_matching("_").await.unwrap(), 1);
+        assert_eq!(repo.complete_matching("%").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_reopen_all() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        assert_eq!(repo.complete_matching("todo 1").await.unwrap(), 1);
+        assert_eq!(repo.complete_matching("todo 3").await.unwrap(), 1);
+        let open = repo.get(2).await.unwrap();
+        assert_eq!(repo.reopen_all().await.unwrap(), 2);
+        for id in 1..=3 {
+            assert!(!repo.get(id).await.unwrap().completed);
+        }
+        assert_eq!(repo.get(2).await.unwrap().updated_at, open.updated_at);
+        assert_eq!(repo.reopen_all().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_first_open_and_latest() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        let update = UpdateTodo {
+            completed: Some(true),
+            ..Default::default()
+        };
+        repo.update(1, update).await.unwrap();
+        assert_eq!(repo.first_open().await.unwrap().unwrap().id, 2);
+        assert_eq!(repo.latest().await.unwrap().unwrap().id, 3);
+    }
+
+    #[tokio::test]
+    async fn test_first_open_and_latest_empty() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        assert!(repo.first_open().await.unwrap().is_none());
+        assert!(repo.latest().await.unwrap().is_none());
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let update = UpdateTodo {
+            completed: Some(true),
+            ..Default::default()
+        };
+        repo.update(1, update).await.unwrap();
+        assert!(repo.first_open().await.unwrap().is_none());
+    }
+
+    /// Collects `message` and `method` of every warning
+    #[derive(Clone, Default)]
+    struct Warnings(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Warnings {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            struct Visitor<'a>(&'a mut String);
+            impl tracing::field::Visit for Visitor<'_> {
+                fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+                    if matches!(field.name(), "message" | "method") {
+                        self.0.push_str(&format!("{}={:?} ", field.name(), value));
+                    }
+                }
+            }
+            if *event.metadata().level() == tracing::Level::WARN {
+                let mut line = String::new();
+                event.record(&mut Visitor(&mut line));
+                self.0.lock().unwrap().push(line);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_query_warning() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let repo = create_repo_and_table().await.unwrap();
+        let warnings = Warnings::default();
+        let subscriber = tracing_subscriber::registry().with(warnings.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let threshold = Duration::from_millis(5);
+        let slow = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            sqlx::query("SELECT 1").execute(&repo.pool).await
+        };
+        let span = tracing::info_span!("slow_method");
+        timed_with(threshold, slow).instrument(span).await.unwrap();
+        let fast = sqlx::query("SELECT 1").execute(&repo.pool);
+        timed_with(Duration::from_secs(60), fast).await.unwrap();
+
+        let warnings = warnings.0.lock().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("slow query"));
+        assert!(warnings[0].contains("method=\"slow_method\""));
+    }
+
+    #[test]
+    fn test_slow_query_threshold_from() {
+        let default = Duration::from_millis(DEFAULT_SLOW_QUERY_MS);
+        assert_eq!(slow_query_threshold_from(None), default);
+        assert_eq!(slow_query_threshold_from(Some(" ".to_owned())), default);
+        assert_eq!(slow_query_threshold_from(Some("abc".to_owned())), default);
+        assert_eq!(
+            slow_query_threshold_from(Some("50".to_owned())),
+            Duration::from_millis(50)
+        );
+    }
+
+    #[test]
+    fn test_validate_create() {
+        let todo = CreateTodo {
+            title: "Buy milk".to_owned(),
+            ..Default::default()
+        };
+        assert!(todo.validate().is_ok());
+        let todo = CreateTodo {
+            title: "x".repeat(MAX_TITLE_LEN + 1),
+            ..Default::default()
+        };
+        assert_eq!(todo.validate().unwrap_err().violations.len(), 1);
+    }
+
+    #[test]
+    fn test_validate_update_reports_all_violations() {
+        let update = UpdateTodo {
+            title: Some(" ".to_owned()),
+            notes: Some("x".repeat(MAX_NOTES_LEN + 1)),
+            ..Default::default()
+        };
+        let err = update.validate().unwrap_err();
+        assert_eq!(
+            err.violations,
+            vec![
+                "title must not be empty".to_owned(),
+                format!("notes must be at most {MAX_NOTES_LEN} characters"),
+            ]
+        );
+        assert!(UpdateTodo::default().validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_count_by_completion() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        assert_eq!(repo.count_by_completion().await.unwrap(), (0, 0));
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3", "Test todo 4"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        for id in [2, 4, 3] {
+            let update = UpdateTodo {
+                completed: Some(true),
+                ..Default::default()
+            };
+            repo.update(id, update).await.unwrap();
+        }
+        assert_eq!(repo.count_by_completion().await.unwrap(), (1, 3));
+    }
+
+    #[test]
+    fn test_id_serde() {
+        let id =
+            id_serde::serialize(&9_007_199_254_740_993, serde_json::value::Serializer).unwrap();
+        assert_eq!(id, serde_json::json!("9007199254740993"));
+        for input in [serde_json::json!(42), serde_json::json!("42")] {
+            assert_eq!(id_serde::deserialize(input).unwrap(), 42);
+        }
+        assert!(id_serde::deserialize(serde_json::json!("abc")).is_err());
+    }
+
+    #[cfg(feature = "string-ids")]
+    #[tokio::test]
+    async fn test_id_serialized_as_string() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let id = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let json = serde_json::to_value(repo.get(id).await.unwrap()).unwrap();
+        assert_eq!(json["id"], serde_json::json!(id.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_last_modified() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        assert_eq!(repo.last_modified().await.unwrap(), None);
+        for (id, updated_at) in [(1, "2024-03-02 10:00:00"), (2, "2024-03-05 08:30:00")] {
+            let todo = Todo {
+                updated_at: datetime(updated_at),
+                ..Todo::fixture(id, datetime("2024-03-01 09:00:00"))
+            };
+            repo.upsert(todo).await.unwrap();
+        }
+        assert_eq!(
+            repo.last_modified().await.unwrap(),
+            Some(datetime("2024-03-05 08:30:00"))
+        );
+        repo.delete(1).await.unwrap();
+        assert!(repo.last_modified().await.unwrap().unwrap() > datetime("2024-03-05 08:30:00"));
+    }
+
+    #[tokio::test]
+    async fn test_not_found_error() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        assert!(matches!(
+            repo.get(42).await,
+            Err(RepositoryError::NotFound(42))
+        ));
+        assert!(matches!(
+            repo.assign(42, "alice").await,
+            Err(RepositoryError::NotFound(42))
+        ));
+        assert!(matches!(
+            repo.merge(1, 7).await,
+            Err(RepositoryError::NotFound(7))
+        ));
+        assert!(matches!(
+            repo.merge(1, 1).await,
+            Err(RepositoryError::Validation(_))
+        ));
+        let err = repo.get(42).await.unwrap_err();
+        assert_eq!(err.to_string(), "todo 42 not found");
+    }
+
+    #[tokio::test]
+    async fn test_import_json() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let json = r#"[
+            {"title": "Buy milk", "notes": "two liters", "completed": 1},
+            {"notes": "no title"},
+            {"title": "Walk the dog", "priority": "high"}
+        ]"#;
+        let report = repo.import_json(json.as_bytes()).await.unwrap();
+        assert_eq!((report.imported, report.skipped), (2, 1));
+        assert_eq!(report.errors[0].0, 2);
+        let todo = repo.get(1).await.unwrap();
+        assert_eq!(todo.notes, "two liters");
+        assert!(todo.completed);
+        assert_eq!(repo.get(2).await.unwrap().priority, Priority::High);
+        assert!(matches!(
+            repo.import_json("title\nBuy milk".as_bytes()).await,
+            Err(RepositoryError::Validation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_import_jsonl() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let jsonl = "{\"title\": \"Buy milk\"}\n\nnot json\n{\"title\": \"Walk the dog\"}\n";
+        let report = repo.import_jsonl(jsonl.as_bytes()).await.unwrap();
+        assert_eq!((report.imported, report.skipped), (2, 1));
+        assert_eq!(report.errors[0].0, 3);
+        assert_eq!(repo.get(2).await.unwrap().title, "Walk the dog");
+
+        // what export writes can be imported again
+        let mut exported = Vec::new();
+        repo.export(ExportFormat::Jsonl, true, &mut exported)
+            .await
+            .unwrap();
+        let report = repo
+            .import(ExportFormat::Jsonl, exported.as_slice())
+            .await
+            .unwrap();
+        assert_eq!(report.imported, 2);
+        assert_eq!(repo.count().await.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_import_csv() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let csv = "title,notes,completed,priority\n\
+            Buy milk,,false,high\n\
+            Call mum,about sunday,true,\n\
+            Fix bike,,maybe,low\n\
+            ,empty title,false,low\n\
+            Water plants\n";
+        let report = repo.import_csv(csv.as_bytes()).await.unwrap();
+        assert_eq!(report.imported, 3);
+        assert_eq!(report.skipped, 2);
+        let lines: Vec<usize> = report.errors.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![4, 5]);
+        assert_eq!(report.errors[1].1, "title must not be empty");
+        assert_eq!(repo.count().await.unwrap(), 3);
+        let todo = repo.get(2).await.unwrap();
+        assert_eq!(todo.notes, "about sunday");
+        assert!(todo.completed);
+        assert_eq!(repo.get(1).await.unwrap().priority, Priority::High);
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        let mut jsonl = Vec::new();
+        assert_eq!(
+            repo.export(ExportFormat::Jsonl, true, &mut jsonl)
+                .await
+                .unwrap(),
+            2
+        );
+        let todos: Vec<Todo> = String::from_utf8(jsonl)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            todos,
+            repo.list_sorted(Pagination::default(), SortBy::Id)
+                .await
+                .unwrap()
+        );
+
+        let mut csv = Vec::new();
+        repo.export(ExportFormat::Csv, true, &mut csv)
+            .await
+            .unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("id,title,notes,completed,assigned,priority,parent_id,position,created_at,updated_at,completed_at,due_date")
+        );
+        assert_eq!(lines.count(), 2);
+        // exported CSV can be imported again
+        let report = repo.import_csv(csv.as_bytes()).await.unwrap();
+        assert_eq!(report.imported, 2);
+
+        let mut rows = Vec::new();
+        repo.export(ExportFormat::Csv, false, &mut rows)
+            .await
+            .unwrap();
+        let rows = String::from_utf8(rows).unwrap();
+        let mut lines = rows.lines();
+        assert!(lines.next().unwrap().starts_with("1,Test todo 1,"));
+        assert_eq!(lines.count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_page() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for n in 1..=5 {
+            let _ = create_todo(&mut repo, &format!("Test todo {n}"))
+                .await
+                .unwrap();
+        }
+        let page = repo.page(2, 2).await.unwrap();
+        assert_eq!(page.total, 5);
+        assert_eq!((page.page, page.per_page), (2, 2));
+        let ids: Vec<i64> = page.items.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![3, 4]);
+        let last = repo.page(3, 2).await.unwrap();
+        assert_eq!(last.items.len(), 1);
+        assert!(repo.page(4, 2).await.unwrap().items.is_empty());
+        assert!(matches!(
+            repo.page(0, 2).await,
+            Err(RepositoryError::Validation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_unique() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let todo = CreateTodo {
+            title: "Buy milk".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(repo.create_unique(todo.clone()).await.unwrap(), Some(1));
+        assert_eq!(repo.create_unique(todo.clone()).await.unwrap(), None);
+        assert_eq!(repo.count().await.unwrap(), 1);
+        // plain create still allows duplicates
+        assert_eq!(repo.create(todo).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_create_unique_index() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        crate::db::set_unique_titles(&repo.pool, true)
+            .await
+            .unwrap();
+        let todo = CreateTodo {
+            title: "Buy milk".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(repo.create_unique(todo.clone()).await.unwrap(), Some(1));
+        assert_eq!(repo.create_unique(todo.clone()).await.unwrap(), None);
+        assert!(matches!(
+            repo.create(todo).await,
+            Err(RepositoryError::Conflict(_))
+        ));
+        assert_eq!(repo.count().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_random_open() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        assert!(repo.random_open().await.unwrap().is_none());
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        for id in [1, 3] {
+            let update = UpdateTodo {
+                completed: Some(true),
+                ..Default::default()
+            };
+            repo.update(id, update).await.unwrap();
+        }
+        for _ in 0..5 {
+            assert_eq!(repo.random_open().await.unwrap().unwrap().id, 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_created_between() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for (id, created_at) in [
+            (1, "2023-12-31 23:59:59"),
+            (2, "2024-01-01 00:00:00"),
+            (3, "2024-01-15 12:00:00"),
+            (4, "2024-02-01 00:00:00"),
+        ] {
+            repo.upsert(Todo::fixture(id, datetime(created_at)))
+                .await
+                .unwrap();
+        }
+        let ids = |todos: Vec<Todo>| todos.iter().map(|todo| todo.id).collect::<Vec<_>>();
+        let since = Some(datetime("2024-01-01 00:00:00"));
+        let until = Some(datetime("2024-02-01 00:00:00"));
+        // `since` is inclusive, `until` exclusive
+        let todos = repo
+            .list_created_between(since, until, Pagination::default())
+            .await
+            .unwrap();
+        assert_eq!(ids(todos), vec![2, 3]);
+        let todos = repo
+            .list_created_between(since, None, Pagination::default())
+            .await
+            .unwrap();
+        assert_eq!(ids(todos), vec![2, 3, 4]);
+        let todos = repo
+            .list_created_between(None, until, Pagination::default())
+            .await
+            .unwrap();
+        assert_eq!(ids(todos), vec![1, 2, 3]);
     }
-    /// Get list of todos support pagination.
-    pub async fn list(&mut self, pagination: Pagination) -> Result<Vec<Todo>> {
-        let todos: Vec<Todo> = sqlx::query_as(
-            "SELECT * FROM todos ORDER BY id LIMIT ?1 OFFSET ?2;",
-        )
-        .bind(pagination.limit.unwrap_or(u32::MAX))
-        .bind(pagination.offset.unwrap_or(0))
-        .fetch_all(&self.pool)
-        .await?;
-        Ok(todos)
+
+    #[tokio::test]
+    async fn test_set_priority_many() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3", "Test todo 4"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        let changed = repo
+            .set_priority_many(&[1, 3, 42], Priority::High)
+            .await
+            .unwrap();
+        assert_eq!(changed, 2);
+        for (id, priority) in [
+            (1, Priority::High),
+            (2, Priority::Normal),
+            (3, Priority::High),
+            (4, Priority::Normal),
+        ] {
+            assert_eq!(repo.get(id).await.unwrap().priority, priority);
+        }
+        // already high
+        assert_eq!(
+            repo.set_priority_many(&[1], Priority::High).await.unwrap(),
+            0
+        );
+        assert_eq!(repo.set_priority_many(&[], Priority::Low).await.unwrap(), 0);
     }
-    /// Get todo from id
-    pub async fn get(&mut self, id: i64) -> Result<Todo> {
-        let todo: Todo = sqlx::query_as("select * from todos where id = ?1 limit 1")
-            .bind(id)
-            .fetch_one(&self.pool)
-            .await?;
-        Ok(todo)
+
+    #[test]
+    fn test_input_defaults() {
+        let todo: CreateTodo = serde_json::from_str(r#"{"title": "Buy milk"}"#).unwrap();
+        assert_eq!(todo.notes, "");
+        assert!(!todo.completed);
+        assert_eq!(todo.priority, None);
+        let assign: AssignTodo = serde_json::from_str("{}").unwrap();
+        assert_eq!(assign.assigned, "");
+        let update: UpdateTodo = serde_json::from_str("{}").unwrap();
+        assert!(update.title.is_none() && update.notes.is_none() && update.completed.is_none());
+        assert!(serde_json::from_str::<CreateTodo>("{}").is_err());
     }
-    /// Update todo
-    pub async fn update(&mut self, id: i64, update: UpdateTodo) -> Result<u64> {
-        let todo = self.get(id).await?;
-        let rows_affected =
-            sqlx::query("UPDATE todos SET title = ?2, notes = ?3, completed = ?4 where id = ?1 ")
-                .bind(id)
-                .bind(update.title.unwrap_or(todo.title))
-                .bind(update.notes.unwrap_or(todo.notes))
-                .bind(update.completed.unwrap_or(todo.completed))
-                .execute(&self.pool)
-                .await?
-                .rows_affected();
-        Ok(rows_affected)
+
+    #[tokio::test]
+    async fn test_create_with_notes_and_completed() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let todo = CreateTodo {
+            title: "Buy milk".to_owned(),
+            notes: "the organic one".to_owned(),
+            completed: true,
+            ..Default::default()
+        };
+        let id = repo.create(todo).await.unwrap();
+        let todo = repo.get(id).await.unwrap();
+        assert_eq!(todo.notes, "the organic one");
+        assert!(todo.completed);
     }
-    /// Delete todo id
-    pub async fn delete(&mut self, id: i64) -> Result<u64> {
-        Ok(sqlx::query("DELETE from todos where id = ?1")
-            .bind(id)
-            .execute(&self.pool)
-            .await?
-            .rows_affected())
+
+    #[tokio::test]
+    async fn test_touch() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let todo = Todo {
+            notes: "seen".to_owned(),
+            assigned: "alice".to_owned(),
+            priority: Priority::High,
+            updated_at: datetime("2024-01-02 10:00:00"),
+            ..Todo::fixture(1, datetime("2024-01-01 10:00:00"))
+        };
+        repo.upsert(todo.clone()).await.unwrap();
+        assert_eq!(repo.touch(1).await.unwrap(), 1);
+        let touched = repo.get(1).await.unwrap();
+        assert!(touched.updated_at > todo.updated_at);
+        assert_eq!(
+            Todo {
+                updated_at: todo.updated_at,
+                ..touched
+            },
+            todo
+        );
+        assert_eq!(repo.touch(42).await.unwrap(), 0);
     }
-    /// Cleanup todos table
-    pub async fn cleanup(&mut self) -> Result<u64> {
-        Ok(sqlx::query("DELETE from todos")
-            .execute(&self.pool)
-            .await?
-            .rows_affected())
+
+    #[tokio::test]
+    async fn test_move_todo() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        let todo = repo.move_todo(2, Some(Some(1)), Some(3)).await.unwrap();
+        assert_eq!((todo.parent_id, todo.position), (Some(1), 3));
+        let todo = repo.move_todo(3, Some(Some(2)), None).await.unwrap();
+        assert_eq!((todo.parent_id, todo.position), (Some(2), 0));
+        // position only keeps the parent
+        let todo = repo.move_todo(3, None, Some(1)).await.unwrap();
+        assert_eq!((todo.parent_id, todo.position), (Some(2), 1));
+        let todo = repo.move_todo(2, Some(None), None).await.unwrap();
+        assert_eq!((todo.parent_id, todo.position), (None, 3));
+        assert!(matches!(
+            repo.move_todo(2, Some(Some(42)), None).await,
+            Err(RepositoryError::NotFound(42))
+        ));
     }
-}
 
-#[cfg(test)]
-mod test {
+    #[tokio::test]
+    async fn test_move_todo_rejects_cycle() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        repo.move_todo(2, Some(Some(1)), None).await.unwrap();
+        repo.move_todo(3, Some(Some(2)), None).await.unwrap();
+        for (id, parent) in [(1, 3), (1, 1), (2, 3)] {
+            assert!(matches!(
+                repo.move_todo(id, Some(Some(parent)), None).await,
+                Err(RepositoryError::Validation(_))
+            ));
+        }
+        assert_eq!(repo.get(1).await.unwrap().parent_id, None);
+    }
 
-    use super::*;
-    use sqlx::sqlite::SqlitePool;
-    pub async fn create_table(pool: SqlitePool) -> Result<()> {
-        sqlx::query(
-            r#"CREATE TABLE IF NOT EXISTS todos
-        (
-            id          INTEGER PRIMARY KEY NOT NULL,
-            title       TEXT                NOT NULL,
-            notes       TEXT                NOT NULL DEFAULT 'note',
-            completed   BOOLEAN             NOT NULL DEFAULT 0
-        );"#,
-        )
-        .execute(&pool)
-        .await?;
-        Ok(())
+    #[tokio::test]
+    async fn test_tags_and_children() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        repo.add_tag_many(&[1], "work").await.unwrap();
+        repo.add_tag_many(&[1, 2], "home").await.unwrap();
+        assert_eq!(repo.tags(1).await.unwrap(), vec!["home", "work"]);
+        assert!(repo.tags(3).await.unwrap().is_empty());
+        repo.move_todo(3, Some(Some(1)), Some(0)).await.unwrap();
+        repo.move_todo(2, Some(Some(1)), Some(1)).await.unwrap();
+        let ids: Vec<i64> = repo
+            .children(1)
+            .await
+            .unwrap()
+            .iter()
+            .map(|todo| todo.id)
+            .collect();
+        assert_eq!(ids, vec![3, 2]);
+        assert!(repo.children(2).await.unwrap().is_empty());
     }
-    async fn create_repo_and_table() -> Result<TodoRepository> {
-        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
-        let repo = TodoRepository::new(pool);
-        create_table(repo.pool.clone()).await.unwrap();
-        Ok(repo)
+
+    #[tokio::test]
+    async fn test_list_id_range() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for id in 1..=5 {
+            let _ = create_todo(&mut repo, &format!("Test todo {id}"))
+                .await
+                .unwrap();
+        }
+        let ids = |todos: Vec<Todo>| todos.iter().map(|todo| todo.id).collect::<Vec<_>>();
+        assert_eq!(ids(repo.list_id_range(2, 4).await.unwrap()), vec![2, 3, 4]);
+        assert_eq!(ids(repo.list_id_range(5, 9).await.unwrap()), vec![5]);
+        assert!(repo.list_id_range(4, 2).await.unwrap().is_empty());
     }
 
-    async fn create_todo(repo: &mut TodoRepository, text: &str) -> Result<i64> {
-        let create_todo = CreateTodo {
-            title: text.to_owned(),
+    #[tokio::test]
+    async fn test_changes() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        repo.upsert(Todo::fixture(1, datetime("2024-01-01 10:00:00")))
+            .await
+            .unwrap();
+        let since = datetime("2024-06-01 00:00:00");
+        let reader = repo.clone();
+        let changes = move |kind| {
+            let mut repo = reader.clone();
+            async move {
+                repo.changes(since, kind, Pagination::default())
+                    .await
+                    .unwrap()
+                    .iter()
+                    .map(|todo| todo.id)
+                    .collect::<Vec<i64>>()
+            }
         };
-        let id = repo.create(create_todo).await?;
-        Ok(id)
+        assert!(changes(None).await.is_empty());
+        let created = create_todo(&mut repo, "Test todo 2").await.unwrap();
+        repo.set_notes(1, "edited").await.unwrap();
+        assert_eq!(changes(Some(ChangeKind::Created)).await, vec![created]);
+        assert_eq!(changes(Some(ChangeKind::Updated)).await, vec![1]);
+        let mut all = changes(None).await;
+        all.sort();
+        assert_eq!(all, vec![1, created]);
     }
 
     #[tokio::test]
-    async fn test_create() {
+    async fn test_swap_positions() {
         let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        for (id, position) in [(1, 1), (2, 2), (3, 3)] {
+            repo.move_todo(id, None, Some(position)).await.unwrap();
+        }
+        repo.swap_positions(1, 3).await.unwrap();
+        for (id, position) in [(1, 3), (2, 2), (3, 1)] {
+            assert_eq!(repo.get(id).await.unwrap().position, position);
+        }
+        assert!(matches!(
+            repo.swap_positions(1, 42).await,
+            Err(RepositoryError::NotFound(42))
+        ));
+        assert_eq!(repo.get(1).await.unwrap().position, 3);
+    }
 
-        let first_id = create_todo(&mut repo, "Test todo 1").await.unwrap();
-        let second_id = create_todo(&mut repo, "Test todo 2").await.unwrap();
-        let third_id = create_todo(&mut repo, "Test todo 3").await.unwrap();
-        assert_eq!(first_id, 1);
-        assert_eq!(second_id, 2);
-        assert_eq!(third_id, 3);
+    #[tokio::test]
+    async fn test_merge_keeps_subtasks() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        repo.move_todo(3, Some(Some(2)), None).await.unwrap();
+        repo.move_todo(1, Some(Some(2)), None).await.unwrap();
+        repo.add_tag_many(&[2], "work").await.unwrap();
+        repo.merge(1, 2).await.unwrap();
+        assert_eq!(repo.list_by_tag("work").await.unwrap()[0].id, 1);
+        assert_eq!(repo.get(3).await.unwrap().parent_id, Some(1));
+        assert_eq!(repo.get(1).await.unwrap().parent_id, None);
     }
 
     #[tokio::test]
-    async fn test_get() {
+    async fn test_merge_into_nested_subtask() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        // 1 <- 2 <- 3
+        repo.move_todo(2, Some(Some(1)), None).await.unwrap();
+        repo.move_todo(3, Some(Some(2)), None).await.unwrap();
+        assert!(matches!(
+            repo.merge(3, 1).await,
+            Err(RepositoryError::Validation(_))
+        ));
+        assert_eq!(repo.count().await.unwrap(), 3);
+        assert_eq!(repo.get(2).await.unwrap().parent_id, Some(1));
+        // the direct subtask takes over its parent's place
+        repo.merge(2, 1).await.unwrap();
+        assert_eq!(repo.get(2).await.unwrap().parent_id, None);
+        assert_eq!(repo.get(3).await.unwrap().parent_id, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
         let mut repo = create_repo_and_table().await.unwrap();
+        // no subscribers yet, nothing to deliver
         let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 2").await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 3").await.unwrap();
+        let mut events = repo.subscribe();
+        let id = create_todo(&mut repo, "Test todo 2").await.unwrap();
+        assert_eq!(
+            events.recv().await.unwrap(),
+            TodoEvent {
+                kind: TodoEventKind::Created,
+                id
+            }
+        );
+        repo.clone().set_notes(1, "note").await.unwrap();
+        repo.delete(id).await.unwrap();
+        repo.delete(id).await.unwrap();
+        let kinds = [events.recv().await.unwrap(), events.recv().await.unwrap()];
+        assert_eq!(
+            kinds,
+            [
+                TodoEvent {
+                    kind: TodoEventKind::Updated,
+                    id: 1
+                },
+                TodoEvent {
+                    kind: TodoEventKind::Deleted,
+                    id
+                }
+            ]
+        );
+        assert!(events.try_recv().is_err());
+    }
 
-        let todo1 = repo.get(1).await.unwrap();
-        let todo2 = repo.get(2).await.unwrap();
-        let todo3 = repo.get(3).await.unwrap();
+    #[test]
+    fn test_event_capacity_from() {
+        assert_eq!(event_capacity_from(None), DEFAULT_EVENT_CAPACITY);
+        assert_eq!(event_capacity_from(Some("16".to_owned())), 16);
+        assert_eq!(event_capacity_from(Some("0".to_owned())), 1);
+        assert_eq!(
+            event_capacity_from(Some("many".to_owned())),
+            DEFAULT_EVENT_CAPACITY
+        );
+    }
 
-        assert_eq!(todo1.id, 1);
-        assert_eq!(todo2.id, 2);
-        assert_eq!(todo3.id, 3);
-        assert_eq!(todo1.title, String::from("Test todo 1"));
-        assert_eq!(todo2.title, String::from("Test todo 2"));
-        assert_eq!(todo3.title, String::from("Test todo 3"));
+    #[tokio::test]
+    async fn test_slow_subscriber_lags() {
+        let mut repo = create_repo_and_table()
+            .await
+            .unwrap()
+            .with_event_capacity(2);
+        let mut events = repo.subscribe();
+        for i in 0..5 {
+            create_todo(&mut repo, &format!("Test todo {i}"))
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            events.recv().await,
+            Err(broadcast::error::RecvError::Lagged(3))
+        );
+        // the newest events are still there
+        assert_eq!(events.recv().await.unwrap().id, 4);
+        assert_eq!(events.recv().await.unwrap().id, 5);
+        assert!(events.try_recv().is_err());
     }
 
     #[tokio::test]
-    async fn test_update() {
+    async fn test_stats() {
         let mut repo = create_repo_and_table().await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 2").await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 3").await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 4").await.unwrap();
-        let update_text = UpdateTodo {
-            title: Some("Update text only".to_owned()),
-            notes: None,
-            completed: None,
-        };
+        let stats = repo.stats().await.unwrap();
+        assert_eq!((stats.total, stats.open, stats.completed), (0, 0, 0));
+        assert_eq!(stats.by_priority.len(), Priority::ALL.len());
+        repo.create_many(&[
+            CreateTodo {
+                title: "Test todo 1".to_owned(),
+                priority: Some(Priority::High),
+                ..Default::default()
+            },
+            CreateTodo {
+                title: "Test todo 2".to_owned(),
+                completed: true,
+                ..Default::default()
+            },
+            CreateTodo {
+                title: "Test todo 3".to_owned(),
+                priority: Some(Priority::High),
+                completed: true,
+                ..Default::default()
+            },
+        ])
+        .await
+        .unwrap();
+        let stats = repo.stats().await.unwrap();
+        assert_eq!((stats.total, stats.open, stats.completed), (3, 1, 2));
+        assert_eq!(stats.by_priority[&Priority::High], 2);
+        assert_eq!(stats.by_priority[&Priority::Normal], 1);
+        assert_eq!(stats.by_priority[&Priority::Low], 0);
+    }
 
-        let update_done = UpdateTodo {
-            title: None,
-            notes: None,
-            completed: Some(true),
-        };
-        let update_both = UpdateTodo {
-            title: Some("Update text and complete status".to_owned()),
-            notes: None,
-            completed: Some(true),
-        };
-        let update_all = UpdateTodo {
-            title: Some("Update title , note and complete status".to_owned()),
-            notes: Some("Some new notes".to_owned()),
-            completed: Some(true),
+    #[tokio::test]
+    async fn test_count_overdue() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let todo = |title: &str, due: Option<(u32, u32)>, completed: bool| CreateTodo {
+            title: title.to_owned(),
+            completed,
+            due_date: due.and_then(|(month, day)| NaiveDate::from_ymd_opt(2024, month, day)),
+            ..Default::default()
         };
+        repo.create_many(&[
+            todo("Overdue", Some((3, 1)), false),
+            todo("Long overdue", Some((1, 15)), false),
+            todo("Due today", Some((3, 10)), false),
+            todo("Future", Some((4, 1)), false),
+            todo("Done late", Some((2, 1)), true),
+            todo("No deadline", None, false),
+        ])
+        .await
+        .unwrap();
+        assert_eq!(repo.count_overdue(today).await.unwrap(), 2);
+        assert_eq!(
+            repo.count_overdue(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap())
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(repo.stats_on(today).await.unwrap().overdue, 2);
+    }
 
-        let update_second_id = repo.update(2, update_text).await.unwrap();
-        let update_third_id = repo.update(3, update_done).await.unwrap();
-        let update_fourth_id = repo.update(4, update_both).await.unwrap();
-        let _ = repo.update(1, update_all).await.unwrap();
+    #[tokio::test]
+    async fn test_tag_many() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        assert_eq!(repo.add_tag_many(&[1, 3, 42], "work").await.unwrap(), 2);
+        assert_eq!(repo.add_tag_many(&[1, 2], " work ").await.unwrap(), 1);
+        assert_eq!(repo.add_tag_many(&[2], "home").await.unwrap(), 1);
+        let tagged = repo.list_by_tag("work").await.unwrap();
+        let ids: Vec<i64> = tagged.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
 
-        assert_eq!(update_second_id, 1);
-        assert_eq!(update_third_id, 1);
-        assert_eq!(update_fourth_id, 1);
+        assert_eq!(repo.remove_tag_many(&[1, 2], "work").await.unwrap(), 2);
+        assert_eq!(repo.remove_tag_many(&[1], "work").await.unwrap(), 0);
+        let tagged = repo.list_by_tag("work").await.unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(repo.list_by_tag("home").await.unwrap()[0].id, 2);
+        // tags go away with their todo
+        repo.delete(3).await.unwrap();
+        assert!(repo.list_by_tag("work").await.unwrap().is_empty());
+        assert!(matches!(
+            repo.add_tag_many(&[1], "  ").await,
+            Err(RepositoryError::Validation(_))
+        ));
+        assert!(matches!(
+            repo.remove_tag_many(&[1], "  ").await,
+            Err(RepositoryError::Validation(_))
+        ));
+    }
 
-        let todo1 = repo.get(1).await.unwrap();
-        let todo2 = repo.get(2).await.unwrap();
-        let todo3 = repo.get(3).await.unwrap();
-        let todo4 = repo.get(4).await.unwrap();
-        println!("{todo1:?}");
-        println!("{todo2:?}");
-        println!("{todo3:?}");
-        println!("{todo4:?}");
-        assert_eq!(todo1.id, 1);
-        assert_eq!(todo2.id, 2);
-        assert_eq!(todo3.id, 3);
-        assert_eq!(todo1.title, String::from("Update title , note and complete status"));
-        assert_eq!(todo2.title, String::from("Update text only"));
-        assert_eq!(todo3.title, String::from("Test todo 3"));
+    #[tokio::test]
+    async fn test_tag_many_add_and_remove() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        repo.add_tag_many(&[1], "inbox").await.unwrap();
+        let changes = repo
+            .tag_many(&[1, 2], Some("work"), Some("inbox"))
+            .await
+            .unwrap();
         assert_eq!(
-            todo4.title,
-            String::from("Update text and complete status")
+            changes,
+            TagChanges {
+                added: 2,
+                removed: 1
+            }
         );
-        assert_eq!(todo1.notes, String::from("Some new notes"));
-        assert!(todo1.completed);
-        assert!(!todo2.completed);
-        assert!(todo3.completed);
-        assert!(todo4.completed);
+        assert!(repo.list_by_tag("inbox").await.unwrap().is_empty());
+        // a bad tag on either side changes nothing
+        let long = "x".repeat(MAX_TAG_LEN + 1);
+        assert!(matches!(
+            repo.tag_many(&[1, 2], Some("home"), Some(&long)).await,
+            Err(RepositoryError::Validation(_))
+        ));
+        assert!(repo.list_by_tag("home").await.unwrap().is_empty());
     }
 
     #[tokio::test]
-    async fn test_list() {
+    async fn test_close() {
         let mut repo = create_repo_and_table().await.unwrap();
         let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 2").await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 3").await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 4").await.unwrap();
-        let pagination = Pagination {
-            offset: None,
-            limit: None,
-        };
-        let todos = repo.list(pagination).await.unwrap();
-        println!("{todos:?}");
-        assert_eq!(todos.len(), 4);
-        // test offset only
-        let todos = repo.list(Pagination::new(Some(1), None)).await.unwrap();
-        println!("{todos:?}");
-        assert_eq!(todos.len(), 3);
-        assert_eq!(todos.get(0).unwrap().id, 2);
-        // test limit  only
-        let todos = repo.list(Pagination::new(None, Some(3))).await.unwrap();
-        println!("{todos:?}");
-        assert_eq!(todos.len(), 3);
-        assert_eq!(todos.get(0).unwrap().id, 1);
-        // test offset and limit
-        let todos = repo.list(Pagination::new(Some(1), Some(2))).await.unwrap();
-        println!("{todos:?}");
-        assert_eq!(todos.len(), 2);
+        let mut clone = repo.clone();
+        assert!(!repo.pool().is_closed());
+        repo.close().await;
+        assert!(clone.pool().is_closed());
+        assert!(matches!(
+            clone.get(1).await,
+            Err(RepositoryError::Database(sqlx::Error::PoolClosed))
+        ));
+    }
+
+    #[test]
+    fn test_lenient_completed() {
+        for (completed, expected) in [
+            (serde_json::json!(true), true),
+            (serde_json::json!(false), false),
+            (serde_json::json!(1), true),
+            (serde_json::json!(0), false),
+            (serde_json::json!("true"), true),
+            (serde_json::json!("false"), false),
+        ] {
+            let create: CreateTodo =
+                serde_json::from_value(serde_json::json!({"title": "x", "completed": completed}))
+                    .unwrap();
+            assert_eq!(create.completed, expected);
+            let update: UpdateTodo =
+                serde_json::from_value(serde_json::json!({ "completed": completed })).unwrap();
+            assert_eq!(update.completed, Some(expected));
+        }
+        let create: CreateTodo = serde_json::from_value(serde_json::json!({"title": "x"})).unwrap();
+        assert!(!create.completed);
+        for json in [
+            serde_json::json!({}),
+            serde_json::json!({ "completed": null }),
+        ] {
+            let update: UpdateTodo = serde_json::from_value(json).unwrap();
+            assert_eq!(update.completed, None);
+        }
+        for completed in [
+            serde_json::json!(2),
+            serde_json::json!("yes"),
+            serde_json::json!([true]),
+        ] {
+            let err = serde_json::from_value::<UpdateTodo>(serde_json::json!({
+                "completed": completed
+            }));
+            assert!(err.is_err());
+        }
+        let err = serde_json::from_str::<CreateTodo>(r#"{"title": "x", "completed": "yes"}"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid boolean `yes`"));
     }
+
+    #[test]
+    fn test_create_todo_builder() {
+        let todo = CreateTodo::builder()
+            .title("Buy milk")
+            .notes("two liters")
+            .completed(true)
+            .priority(Priority::High)
+            .build()
+            .unwrap();
+        assert_eq!(todo.title, "Buy milk");
+        assert_eq!(todo.notes, "two liters");
+        assert!(todo.completed);
+        assert_eq!(todo.priority, Some(Priority::High));
+
+        let todo = CreateTodo::builder().title("Buy milk").build().unwrap();
+        assert_eq!(todo.notes, "");
+        assert!(!todo.completed);
+        assert_eq!(todo.priority, None);
+
+        let err = CreateTodo::builder().notes("no title").build().unwrap_err();
+        assert_eq!(err.violations, vec!["title must not be empty"]);
+    }
+
+    #[test]
+    fn test_update_todo_builder() {
+        let update = UpdateTodo::builder()
+            .title("Renamed")
+            .notes("")
+            .completed(false)
+            .priority(Priority::Low)
+            .build()
+            .unwrap();
+        assert_eq!(update.title.as_deref(), Some("Renamed"));
+        assert_eq!(update.notes.as_deref(), Some(""));
+        assert_eq!(update.completed, Some(false));
+        assert_eq!(update.priority, Some(Priority::Low));
+
+        let update = UpdateTodo::builder().build().unwrap();
+        assert!(update.title.is_none() && update.completed.is_none());
+        assert!(UpdateTodo::builder().title(" ").build().is_err());
+    }
+
     #[tokio::test]
-    async fn test_delete() {
+    async fn test_for_each_page() {
         let mut repo = create_repo_and_table().await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 1").await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 2").await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 3").await.unwrap();
-        let _ = create_todo(&mut repo, "Test todo 4").await.unwrap();
+        for id in 1..=7 {
+            let _ = create_todo(&mut repo, &format!("Test todo {id}"))
+                .await
+                .unwrap();
+        }
+        let mut pages = Vec::new();
+        let mut seen = Vec::new();
+        for_each_page(&mut repo, 3, |todos| {
+            pages.push(todos.len());
+            seen.extend(todos.into_iter().map(|todo| todo.id));
+        })
+        .await
+        .unwrap();
+        assert_eq!(pages, vec![3, 3, 1]);
+        seen.sort();
+        assert_eq!(seen, (1..=7).collect::<Vec<i64>>());
+        // the empty page after a full last one is not passed on
+        let mut pages = Vec::new();
+        for_each_page(&mut repo, 7, |todos| pages.push(todos.len()))
+            .await
+            .unwrap();
+        assert_eq!(pages, vec![7]);
+        assert!(for_each_page(&mut repo, 0, |_| {}).await.is_err());
+    }
 
-        let affected_rows = repo.delete(3).await.unwrap();
-        assert_eq!(affected_rows, 1);
-        let todos = repo
-            .list(Pagination {
-                offset: None,
-                limit: None,
+    #[tokio::test]
+    async fn test_find_by_title() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let first = create_todo(&mut repo, "Buy milk").await.unwrap();
+        let _ = create_todo(&mut repo, "Buy milk").await.unwrap();
+        let todo = repo.find_by_title("Buy milk").await.unwrap().unwrap();
+        assert_eq!(todo.id, first);
+        assert!(repo.find_by_title("buy milk").await.unwrap().is_none());
+        assert!(repo.find_by_title("Buy").await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_normalize_title() {
+        assert_eq!(normalize_title("  buy   milk  "), "buy milk");
+        assert_eq!(normalize_title("buy\tmilk\n"), "buy milk");
+        assert_eq!(normalize_title("buy milk"), "buy milk");
+        assert_eq!(normalize_title("   "), "");
+    }
+
+    #[tokio::test]
+    async fn test_titles_normalized() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let id = create_todo(&mut repo, "  buy   milk  ").await.unwrap();
+        assert_eq!(repo.get(id).await.unwrap().title, "buy milk");
+        let duplicate = repo
+            .create_unique(CreateTodo {
+                title: "buy  milk ".to_owned(),
+                ..Default::default()
             })
             .await
             .unwrap();
-        println!("{todos:?}");
-        assert_eq!(todos.len(), 3);
+        assert_eq!(duplicate, None);
+        let update = UpdateTodo {
+            title: Some(" buy\toat   milk".to_owned()),
+            ..Default::default()
+        };
+        let todo = repo.update(id, update).await.unwrap().unwrap();
+        assert_eq!(todo.title, "buy oat milk");
+    }
+
+    #[tokio::test]
+    async fn test_set_order() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        for text in ["Test todo 1", "Test todo 2", "Test todo 3"] {
+            let _ = create_todo(&mut repo, text).await.unwrap();
+        }
+        repo.set_order(&[3, 1, 2]).await.unwrap();
+        for (id, position) in [(3, 0), (1, 1), (2, 2)] {
+            assert_eq!(repo.get(id).await.unwrap().position, position);
+        }
+        let err = repo.set_order(&[3, 1, 1, 42]).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "order must list every todo once: missing 2; unknown 42; repeated 1"
+        );
+        // a rejected order leaves the positions alone
+        assert_eq!(repo.get(3).await.unwrap().position, 0);
+    }
+
+    #[tokio::test]
+    async fn test_shift_due_dates() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        let day = |month, day| NaiveDate::from_ymd_opt(2024, month, day);
+        let todo = |title: &str, due_date, completed| CreateTodo {
+            title: title.to_owned(),
+            completed,
+            due_date,
+            ..Default::default()
+        };
+        let ids = repo
+            .create_many(&[
+                todo("Open", day(3, 28), false),
+                todo("No deadline", None, false),
+                todo("Done", day(3, 1), true),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(repo.shift_due_dates(7).await.unwrap(), 1);
+        let due_dates = [
+            repo.get(ids[0]).await.unwrap().due_date,
+            repo.get(ids[1]).await.unwrap().due_date,
+            repo.get(ids[2]).await.unwrap().due_date,
+        ];
+        assert_eq!(due_dates, [day(4, 4), None, day(3, 1)]);
+        assert_eq!(repo.shift_due_dates(-10).await.unwrap(), 1);
+        assert_eq!(repo.get(ids[0]).await.unwrap().due_date, day(3, 25));
+    }
+
+    #[tokio::test]
+    async fn test_all_completed() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        assert!(!repo.all_completed().await.unwrap());
+        let first = create_todo(&mut repo, "Test todo 1").await.unwrap();
+        let second = create_todo(&mut repo, "Test todo 2").await.unwrap();
+        let done = || UpdateTodo {
+            completed: Some(true),
+            ..Default::default()
+        };
+        repo.update(first, done()).await.unwrap();
+        assert!(!repo.all_completed().await.unwrap());
+        repo.update(second, done()).await.unwrap();
+        assert!(repo.all_completed().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_distinct_assignees() {
+        let mut repo = create_repo_and_table().await.unwrap();
+        assert!(repo.distinct_assignees().await.unwrap().is_empty());
+        for (title, person) in [
+            ("Test todo 1", "mia"),
+            ("Test todo 2", "ben"),
+            ("Test todo 3", "mia"),
+            ("Test todo 4", ""),
+            ("Test todo 5", "ada"),
+        ] {
+            let id = create_todo(&mut repo, title).await.unwrap();
+            repo.assign(id, person).await.unwrap();
+        }
+        let _ = create_todo(&mut repo, "Test todo 6").await.unwrap();
+        assert_eq!(
+            repo.distinct_assignees().await.unwrap(),
+            vec!["ada", "ben", "mia"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_seed() {
+        let seed = [
+            CreateTodo {
+                title: "Seed todo 1".to_owned(),
+                ..Default::default()
+            },
+            CreateTodo {
+                title: "Seed todo 2".to_owned(),
+                ..Default::default()
+            },
+        ];
+        let mut repo = TodoRepository::in_memory_with(&seed).await.unwrap();
+        let todos = repo.list(Pagination::default()).await.unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[0].title, String::from("Seed todo 1"));
+        assert_eq!(todos[1].title, String::from("Seed todo 2"));
+        assert!(!todos[1].completed);
     }
 }